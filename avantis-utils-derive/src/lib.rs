@@ -6,17 +6,19 @@ use syn::DeriveInput;
 
 use self::models::PaginatedStruct;
 
-#[proc_macro_derive(PaginatedQuery, attributes(limit, offset))]
+#[proc_macro_derive(PaginatedQuery, attributes(limit, offset, page, page_size))]
 pub fn paginated_query_macro_derive(input: TokenStream) -> TokenStream {
     let syntax_tree = parse_macro_input!(input as DeriveInput);
 
-    let model = PaginatedStruct::try_from(&syntax_tree).unwrap();
-
-    model.gen().into()
+    match PaginatedStruct::try_from(&syntax_tree) {
+        Ok(model) => model.gen().into(),
+        Err(err) => syn::Error::new_spanned(&syntax_tree.ident, err)
+            .to_compile_error()
+            .into(),
+    }
 }
 
 mod models {
-    use proc_macro2::Span;
     use proc_macro2::TokenStream;
     use quote::quote;
     use syn::*;
@@ -24,76 +26,184 @@ mod models {
     #[derive(Clone, Debug)]
     pub(super) struct PaginatedStruct {
         name: Ident,
-        limit: PaginatedStructField,
-        offset: PaginatedStructField,
+        generics: Generics,
+        kind: PaginatedStructKind,
+    }
+
+    #[derive(Clone, Debug)]
+    enum PaginatedStructKind {
+        /// Struct with named fields marked by `#[limit]` and `#[offset]`.
+        Fields {
+            limit: PaginatedStructField,
+            offset: PaginatedStructField,
+        },
+        /// Struct with named fields marked by `#[page]` and `#[page_size]`.
+        /// Offset is computed as `(page - 1) * page_size`, with page clamped to at least 1.
+        Page {
+            page: PaginatedStructField,
+            page_size: PaginatedStructField,
+        },
+        /// Single-field tuple struct delegating to its inner `PaginatedQuery`.
+        Delegate(Type),
     }
 
     impl PaginatedStruct {
         pub(super) fn gen(&self) -> TokenStream {
             let name = &self.name;
-            let limit_fn = &self.limit.gen("limit");
-            let offset_fn = &self.offset.gen("offset");
+            let mut generics = self.generics.clone();
+            let i32_fns = gen_i32_fns();
 
-            quote! {
-                impl PaginatedQuery for #name {
-                    #limit_fn
+            let fns = match &self.kind {
+                PaginatedStructKind::Fields { limit, offset } => {
+                    let limit_value = limit.value();
+                    let offset_value = offset.value();
+
+                    quote! {
+                        #i32_fns
+
+                        fn limit_i64(&self) -> i64 {
+                            #limit_value
+                        }
+
+                        fn offset_i64(&self) -> i64 {
+                            #offset_value
+                        }
+                    }
+                }
+                PaginatedStructKind::Page { page, page_size } => {
+                    let page_value = page.value();
+                    let page_size_value = page_size.value();
+
+                    quote! {
+                        #i32_fns
 
-                    #offset_fn
+                        fn limit_i64(&self) -> i64 {
+                            #page_size_value
+                        }
+
+                        fn offset_i64(&self) -> i64 {
+                            let page = std::cmp::max(#page_value, 1);
+
+                            (page - 1).saturating_mul(self.limit_i64())
+                        }
+                    }
+                }
+                PaginatedStructKind::Delegate(ty) => {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(parse_quote! { #ty: PaginatedQuery });
+
+                    quote! {
+                        fn limit(&self) -> i32 {
+                            PaginatedQuery::limit(&self.0)
+                        }
+
+                        fn offset(&self) -> i32 {
+                            PaginatedQuery::offset(&self.0)
+                        }
+
+                        fn limit_i64(&self) -> i64 {
+                            PaginatedQuery::limit_i64(&self.0)
+                        }
+
+                        fn offset_i64(&self) -> i64 {
+                            PaginatedQuery::offset_i64(&self.0)
+                        }
+                    }
                 }
+            };
+
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+            quote! {
+                impl #impl_generics PaginatedQuery for #name #ty_generics #where_clause {
+                    #fns
+                }
+            }
+        }
+    }
+
+    /// `limit()` and `offset()` derived from their `i64` counterparts, saturating at `i32::MAX`.
+    /// Values are never negative, so only the upper bound could overflow.
+    fn gen_i32_fns() -> TokenStream {
+        quote! {
+            fn limit(&self) -> i32 {
+                <i32 as std::convert::TryFrom<i64>>::try_from(self.limit_i64()).unwrap_or(i32::MAX)
+            }
+
+            fn offset(&self) -> i32 {
+                <i32 as std::convert::TryFrom<i64>>::try_from(self.offset_i64()).unwrap_or(i32::MAX)
             }
-            .into()
         }
     }
 
     #[derive(Clone, Debug)]
     struct PaginatedStructField {
         ident_opt: Option<Ident>,
+        int_type: IntType,
         default_value: LitInt,
+        min_value: Option<LitInt>,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum IntType {
+        I32,
+        I64,
     }
 
     impl PaginatedStructField {
-        fn gen(&self, fn_name: &'static str) -> TokenStream {
+        /// Field value as `i64`, falling back to default and clamped to min (or `0`).
+        fn value(&self) -> TokenStream {
             let default_value_lit = &self.default_value;
 
-            let impl_quote = match self.ident_opt.as_ref() {
-                Some(ident) => quote! { self.#ident.unwrap_or(#default_value_lit) },
-                None => quote! { #default_value_lit },
+            let min_value = match self.min_value.as_ref() {
+                Some(min_value_lit) => quote! { #min_value_lit },
+                None => quote! { 0 },
             };
 
-            let fn_name = Ident::new(fn_name, Span::call_site());
-
-            quote! {
-                fn #fn_name(&self) -> i32 {
-                    #impl_quote
-                }
+            match (self.ident_opt.as_ref(), self.int_type) {
+                (Some(ident), IntType::I32) => quote! {
+                    std::cmp::max(self.#ident.map(i64::from).unwrap_or(#default_value_lit), #min_value)
+                },
+                (Some(ident), IntType::I64) => quote! {
+                    std::cmp::max(self.#ident.unwrap_or(#default_value_lit), #min_value)
+                },
+                (None, _) => quote! { std::cmp::max::<i64>(#default_value_lit, #min_value) },
             }
         }
 
         fn limit_field<T>(
             fields: &punctuated::Punctuated<syn::Field, T>,
         ) -> core::result::Result<Self, &'static str> {
-            let matched_fields = fields
-                .iter()
-                .filter(|f| matches!(Attr::try_from(*f), Ok(Attr::Limit(_))))
-                .filter_map(|f| PaginatedStructField::try_from(f).ok())
-                .collect::<Vec<_>>();
+            Self::find_field(fields, |attr| matches!(attr, Attr::Limit(_)))
+        }
 
-            if matched_fields.len() > 1 {
-                return Err("too many attributes");
-            }
+        fn offset_field<T>(
+            fields: &punctuated::Punctuated<syn::Field, T>,
+        ) -> core::result::Result<Self, &'static str> {
+            Self::find_field(fields, |attr| matches!(attr, Attr::Offset(_)))
+        }
 
-            Ok(matched_fields
-                .first()
-                .ok_or_else(|| "field not found")?
-                .clone())
+        fn page_field<T>(
+            fields: &punctuated::Punctuated<syn::Field, T>,
+        ) -> core::result::Result<Self, &'static str> {
+            Self::find_field(fields, |attr| matches!(attr, Attr::Page(_)))
         }
 
-        fn offset_field<T>(
+        fn page_size_field<T>(
+            fields: &punctuated::Punctuated<syn::Field, T>,
+        ) -> core::result::Result<Self, &'static str> {
+            Self::find_field(fields, |attr| matches!(attr, Attr::PageSize(_)))
+        }
+
+        fn find_field<T>(
             fields: &punctuated::Punctuated<syn::Field, T>,
+            is_match: fn(&Attr) -> bool,
         ) -> core::result::Result<Self, &'static str> {
             let matched_fields = fields
                 .iter()
-                .filter(|f| matches!(Attr::try_from(*f), Ok(Attr::Offset(_))))
+                .filter(|f| matches!(Attr::try_from(*f), Ok(ref attr) if is_match(attr)))
                 .filter_map(|f| PaginatedStructField::try_from(f).ok())
                 .collect::<Vec<_>>();
 
@@ -110,17 +220,32 @@ mod models {
 
     #[derive(Clone, Debug)]
     enum Attr {
-        Limit(LitInt),
-        Offset(LitInt),
+        Limit(AttrArgs),
+        Offset(AttrArgs),
+        Page(AttrArgs),
+        PageSize(AttrArgs),
+    }
+
+    /// Arguments of an attribute, ie. `#[limit(default = 100, min = 1)]`.
+    #[derive(Clone, Debug)]
+    struct AttrArgs {
+        default_value: LitInt,
+        min_value: Option<LitInt>,
     }
 
     impl Attr {
-        fn default_value(&self) -> &LitInt {
+        fn args(&self) -> &AttrArgs {
             match self {
-                Attr::Limit(default) => default,
-                Attr::Offset(default) => default,
+                Attr::Limit(args) => args,
+                Attr::Offset(args) => args,
+                Attr::Page(args) => args,
+                Attr::PageSize(args) => args,
             }
         }
+
+        fn is_page(&self) -> bool {
+            matches!(self, Attr::Page(_) | Attr::PageSize(_))
+        }
     }
 
     pub(super) mod extractors {
@@ -130,17 +255,57 @@ mod models {
             type Error = &'static str;
 
             fn try_from(input: &DeriveInput) -> core::result::Result<Self, Self::Error> {
-                match input.data {
+                let kind = match input.data {
                     syn::Data::Struct(syn::DataStruct {
                         fields: syn::Fields::Named(FieldsNamed { ref named, .. }),
                         ..
-                    }) => Ok(PaginatedStruct {
-                        name: input.ident.clone(),
-                        limit: PaginatedStructField::limit_field(&named)?,
-                        offset: PaginatedStructField::offset_field(&named)?,
-                    }),
-                    _ => Err("help!"),
-                }
+                    }) if named
+                        .iter()
+                        .any(|f| matches!(Attr::try_from(f), Ok(ref attr) if attr.is_page())) =>
+                    {
+                        if PaginatedStructField::limit_field(named).is_ok()
+                            || PaginatedStructField::offset_field(named).is_ok()
+                        {
+                            return Err("cannot mix page/page_size with limit/offset attributes");
+                        }
+
+                        PaginatedStructKind::Page {
+                            page: PaginatedStructField::page_field(named)?,
+                            page_size: PaginatedStructField::page_size_field(named)?,
+                        }
+                    }
+                    syn::Data::Struct(syn::DataStruct {
+                        fields: syn::Fields::Named(FieldsNamed { ref named, .. }),
+                        ..
+                    }) => PaginatedStructKind::Fields {
+                        limit: PaginatedStructField::limit_field(named)?,
+                        offset: PaginatedStructField::offset_field(named)?,
+                    },
+                    syn::Data::Struct(syn::DataStruct {
+                        fields: syn::Fields::Unnamed(FieldsUnnamed { ref unnamed, .. }),
+                        ..
+                    }) if unnamed.len() == 1 => {
+                        PaginatedStructKind::Delegate(unnamed[0].ty.clone())
+                    }
+                    syn::Data::Struct(syn::DataStruct {
+                        fields: syn::Fields::Unnamed(_),
+                        ..
+                    }) => return Err("PaginatedQuery on tuple struct requires exactly one field"),
+                    syn::Data::Struct(syn::DataStruct {
+                        fields: syn::Fields::Unit,
+                        ..
+                    }) => return Err("PaginatedQuery cannot be derived for unit struct"),
+                    syn::Data::Enum(_) => return Err("PaginatedQuery cannot be derived for enum"),
+                    syn::Data::Union(_) => {
+                        return Err("PaginatedQuery cannot be derived for union")
+                    }
+                };
+
+                Ok(PaginatedStruct {
+                    name: input.ident.clone(),
+                    generics: input.generics.clone(),
+                    kind,
+                })
             }
         }
 
@@ -149,16 +314,19 @@ mod models {
 
             fn try_from(field: &Field) -> core::result::Result<Self, Self::Error> {
                 let ident_opt = field.ident.clone();
-                let default_value = Attr::try_from(field.attrs.as_slice())?
-                    .default_value()
-                    .clone();
+                let AttrArgs {
+                    default_value,
+                    min_value,
+                } = Attr::try_from(field.attrs.as_slice())?.args().clone();
 
-                match is_option_i32(&field.ty) {
-                    true => Ok(PaginatedStructField {
+                match option_int_type(&field.ty) {
+                    Some(int_type) => Ok(PaginatedStructField {
                         ident_opt,
+                        int_type,
                         default_value,
+                        min_value,
                     }),
-                    false => Err("not option i32"),
+                    None => Err("not option i32 or option i64"),
                 }
             }
         }
@@ -187,28 +355,58 @@ mod models {
             type Error = &'static str;
 
             fn try_from(attr: &Attribute) -> core::result::Result<Self, Self::Error> {
-                let lit = match attr.parse_meta() {
-                    Ok(Meta::List(MetaList { nested, .. })) if nested.len() == 1 => {
-                        match &nested[0] {
-                            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
-                                lit: Lit::Int(lit),
-                                ..
-                            })) => lit.clone(),
-                            _ => return Err("unexpected attributes"),
-                        }
-                    }
+                let args = match attr.parse_meta() {
+                    Ok(Meta::List(MetaList { nested, .. })) => AttrArgs::try_from(&nested)?,
                     _ => return Err("unexpected attributes"),
                 };
 
                 match attr.path.get_ident() {
-                    Some(ident) if ident == "limit" => Ok(Attr::Limit(lit)),
-                    Some(ident) if ident == "offset" => Ok(Attr::Offset(lit)),
+                    Some(ident) if ident == "limit" => Ok(Attr::Limit(args)),
+                    Some(ident) if ident == "offset" => Ok(Attr::Offset(args)),
+                    Some(ident) if ident == "page" => Ok(Attr::Page(args)),
+                    Some(ident) if ident == "page_size" => Ok(Attr::PageSize(args)),
                     _ => Err("unexpected attributes"),
                 }
             }
         }
 
-        fn is_option_i32(ty: &Type) -> bool {
+        impl<T> TryFrom<&punctuated::Punctuated<NestedMeta, T>> for AttrArgs {
+            type Error = &'static str;
+
+            fn try_from(
+                nested: &punctuated::Punctuated<NestedMeta, T>,
+            ) -> core::result::Result<Self, Self::Error> {
+                let mut default_value = None;
+                let mut min_value = None;
+
+                for meta in nested {
+                    match meta {
+                        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                            path,
+                            lit: Lit::Int(lit),
+                            ..
+                        })) if path.is_ident("default") && default_value.is_none() => {
+                            default_value = Some(lit.clone())
+                        }
+                        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                            path,
+                            lit: Lit::Int(lit),
+                            ..
+                        })) if path.is_ident("min") && min_value.is_none() => {
+                            min_value = Some(lit.clone())
+                        }
+                        _ => return Err("unexpected attributes"),
+                    }
+                }
+
+                Ok(AttrArgs {
+                    default_value: default_value.ok_or("missing default value")?,
+                    min_value,
+                })
+            }
+        }
+
+        fn option_int_type(ty: &Type) -> Option<IntType> {
             match ty {
                 Type::Path(TypePath {
                     path: Path { segments, .. },
@@ -226,14 +424,19 @@ mod models {
                             GenericArgument::Type(Type::Path(TypePath { path, .. }))
                                 if path.is_ident("i32") =>
                             {
-                                true
+                                Some(IntType::I32)
+                            }
+                            GenericArgument::Type(Type::Path(TypePath { path, .. }))
+                                if path.is_ident("i64") =>
+                            {
+                                Some(IntType::I64)
                             }
-                            _ => false,
+                            _ => None,
                         }
                     }
-                    _ => false,
+                    _ => None,
                 },
-                _ => false,
+                _ => None,
             }
         }
     }
@@ -16,12 +16,13 @@ mod inner {
     use avantis_utils::config::Environment;
     use avantis_utils::kafka::consumer;
     use avantis_utils::kafka::consumer::ConsumerExt;
+    use avantis_utils::kafka::consumer::LoggingConsumerContext;
     use avantis_utils::kafka::producer::with_trace_header;
     use avantis_utils::kafka::KafkaConfig;
     use avantis_utils::kafka::ProtobufKafkaMessage;
     use avantis_utils::kafka::ProtobufKafkaRecord;
     use avantis_utils::telemetry::TelemetrySetting;
-    use futures_lite::{pin, StreamExt};
+    use futures_lite::StreamExt;
     use once_cell::sync::Lazy;
     use opentelemetry::trace::TraceContextExt;
     use prost;
@@ -33,6 +34,7 @@ mod inner {
     use rdkafka::producer::FutureRecord;
     use rdkafka::util::Timeout;
     use tracing;
+    use tracing::Instrument;
     use tracing_opentelemetry::OpenTelemetrySpanExt;
 
     #[derive(Clone, PartialEq, Message)]
@@ -79,28 +81,26 @@ mod inner {
 
     #[tracing::instrument(skip_all, name = "kafk_simple::consumer")]
     async fn consumer() -> Result<(), anyhow::Error> {
-        let kafka_consumer: StreamConsumer = SETTINGS
+        let kafka_consumer: StreamConsumer<LoggingConsumerContext> = SETTINGS
             .kafka
-            .consumer_config(&SETTINGS.kafka_consumer_group)?;
+            .consumer_config_with_context(&SETTINGS.kafka_consumer_group, LoggingConsumerContext)?;
 
         kafka_consumer.subscribe(&[&SETTINGS.kafka_topic])?;
-        let stream = kafka_consumer
-            .stream()
-            .map(|message| {
-                let result = kafka_consumer.process_protobuf_and_commit(
-                    message,
-                    check_msg,
-                    CommitMode::Sync,
-                );
-                result
-            })
-            .map(|future_result| async move {
-                future_result.await.unwrap_or_else(consumer::process_error)
-            });
-        pin!(stream);
-        Ok(while let Some(future) = stream.next().await {
-            future.await;
-        })
+        let mut stream = kafka_consumer.protobuf_stream::<ProtobufMessage>();
+        while let Some(decoded) = stream.next().await {
+            let decoded = match decoded {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    consumer::process_error(err);
+                    continue;
+                }
+            };
+
+            check_msg(decoded.value).instrument(decoded.span).await?;
+            kafka_consumer.commit_message(&decoded.message, CommitMode::Sync)?;
+        }
+
+        Ok(())
     }
 
     #[tracing::instrument(skip_all, name = "kafk_simple::producer")]
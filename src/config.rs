@@ -14,32 +14,88 @@
 //! 2. Create an environment config file like `config/develop.toml` to your project.
 //! 3. Set env to replace credentials. Use `APP` for prefix and separator `__` for hierarchy.
 //!    For example, `APP_STOCK_DB__PASSWORD` will replace config at field `stock_db.password`.
+//!    `APP__STOCK_DB__PASSWORD` works as well. Single `_` is kept in field names, ie.
+//!    `APP_STOCK_DB__MAX_CONNECTIONS` replaces `stock_db.max_connections`.
 //! 4. In your code, create a config struct which mirror configuration from earlier steps.
 //! 5. Call `load_config` with selected Environment into the struct from step 4.
 //!
 //! For example usage, see [here](https://github.com/ava-global/avantis-rust-utilities/blob/main/examples/config/main.rs)
 //! and its config files [here](https://github.com/ava-global/avantis-rust-utilities/tree/main/config).
 //!
+//! List fields like `Vec<String>` could be overridden by env as well. Build environment variables
+//! source with [environment_variables_with_list_keys] and pass it to [load_custom_config].
+//! For example, `APP_REDIS__HOSTS=a,b,c` will replace config at field `redis.hosts` with `["a", "b", "c"]`.
+//!
+//! Mark credentials with [Secret] to keep them out of logs. To inspect merged config values
+//! of a running service, use [dump_effective], which print [Secret] fields as `***`.
+//!
+//! To keep every environment in a single file with `[default]` and `[<environment>]` sections,
+//! use [load_config_sectioned].
+//!
+//! For "drop-in" config fragments, ie. Kubernetes ConfigMaps mounted as `config/conf.d/*.toml`,
+//! use [load_config_from_dir].
+//!
+//! Credentials could also be read from files, ie. Docker or Kubernetes secrets, following the
+//! `_FILE` convention. For example, `APP_STOCK_DB__PASSWORD_FILE=/run/secrets/db_password`
+//! will replace config at field `stock_db.password` with content of the file. This is opt-in,
+//! use [load_config_with_secret_files] or add [secret_files] as a source.
+//!
+//! To expand `${VAR}` in config values from env, ie. `endpoint = "https://${REGION}.api.internal"`,
+//! use [load_custom_config_interpolated] or run [interpolate] on a merged [config::Config].
+//!
 //! If you need to customize load mechanism, see [load_custom_config] or maybe use [config::Config] directly instead.
 //!
 //! [^1]: Any format listed in [config::FileFormat] can be used.
 
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::Result;
+use config_rs::builder::DefaultState;
 use config_rs::Config;
+use config_rs::ConfigBuilder;
+use config_rs::ConfigError;
 use config_rs::Environment as EnvironmentVariables;
 use config_rs::File;
-use config_rs::FileFormat;
+pub use config_rs::FileFormat;
 use config_rs::FileSourceFile;
+use config_rs::FileStoredFormat;
+use config_rs::Map;
+use config_rs::Source;
+use config_rs::Value;
+use config_rs::ValueKind;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde::Serialize;
 use strum::EnumString;
+use tracing::error;
+use tracing::info;
+use tracing::instrument;
+
+#[cfg(feature = "config-encryption")]
+mod encrypted;
+mod error;
+mod interpolate;
+mod secret;
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+#[cfg(feature = "config-encryption")]
+pub use encrypted::{decrypt_value, encrypt_value, EncryptedString, CONFIG_KEY_ENV};
+pub use error::ConfigDeserializeError;
+pub use interpolate::{interpolate, UnknownVariable};
+pub use secret::Secret;
 
 /// Load config from selected [Environment].
 /// Returns a Result containing config struct.
 /// Convenience [load_custom_config].
 ///
+/// Log an `info` line with elapsed time on success, in a span recording environment and
+/// resolved file paths. Only visible if a subscriber is installed before loading.
+///
 /// # Example
 ///
 /// ```
@@ -58,14 +114,75 @@ use strum::EnumString;
 /// }
 /// ```
 pub fn load_config<'de, T: Deserialize<'de>>(environment: Environment) -> Result<T> {
-    let base_config_file = File::with_name("config/base").required(true);
-    let env_config_file = File::with_name(&format!("config/{}", environment)).required(true);
+    load_config_by_path(environment, DEFAULT_CONFIG_PATH)
+}
 
-    let custom_env_vars = EnvironmentVariables::with_prefix("app")
-        .prefix_separator("_")
-        .separator("__");
+/// Load config from selected [Environment], like [load_config].
+/// If loading fails, log the error and return `T::default()` instead.
+///
+/// Prefer [load_config] for fail-fast behavior. Use this for services which prefer
+/// running degraded over crashing on a bad config.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_config_or_default;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let config: MyConfig = load_config_or_default(Environment::Develop);
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_config_or_default<'de, T: Deserialize<'de> + Default>(environment: Environment) -> T {
+    load_config(environment).unwrap_or_else(|err| {
+        error!(
+            "unable to load config for environment [{}], fallback to default: {}",
+            environment, err
+        );
+        T::default()
+    })
+}
 
-    load_custom_config(base_config_file, env_config_file, custom_env_vars)
+/// Load config from selected [Environment] like [load_config], then replace fields with content
+/// of files pointed by `_FILE` envs, ie. Docker or Kubernetes secrets. See [secret_files].
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_config_with_secret_files;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     // `APP_DB__PASSWORD_FILE=/run/secrets/db_password` replaces `db.password`.
+///     let config: MyConfig = load_config_with_secret_files(Environment::Develop).unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_config_with_secret_files<'de, T: Deserialize<'de>>(
+    environment: Environment,
+) -> Result<T> {
+    let [base_config_name, env_config_name] = config_file_names(DEFAULT_CONFIG_PATH, environment);
+
+    let builder = Config::builder()
+        .add_source(File::with_name(&base_config_name).required(true))
+        .add_source(File::with_name(&env_config_name).required(true))
+        .add_source(environment_variables())
+        .add_source(secret_files());
+
+    load_with(&[&base_config_name, &env_config_name], builder, deserialize)
 }
 
 /// Load config by path from selected [Environment] and [Path].
@@ -93,14 +210,600 @@ pub fn load_config_by_path<'de, T: Deserialize<'de>>(
     environment: Environment,
     path: &str,
 ) -> Result<T> {
-    let base_config_file = File::with_name(&format!("{}/base", path)).required(true);
-    let env_config_file = File::with_name(&format!("{}/{}", path, environment)).required(true);
+    load_custom_config_files(DEFAULT_BASE_NAME, &environment.to_string(), path)
+}
+
+/// Load config from `{path}/{base_name}` and `{path}/{env_name}` files, with env overrides
+/// like [load_config]. Use this when the shared file is not named `base`, ie. `common`.
+/// File names are without extension, format is detected from it.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_custom_config_files;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let environment = Environment::Develop;
+///     let config: MyConfig =
+///         load_custom_config_files("base", &environment.to_string(), "config").unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_custom_config_files<'de, T: Deserialize<'de>>(
+    base_name: &str,
+    env_name: &str,
+    path: &str,
+) -> Result<T> {
+    let base_config_name = format!("{}/{}", path, base_name);
+    let env_config_name = format!("{}/{}", path, env_name);
+
+    let builder = config_builder(
+        File::with_name(&base_config_name).required(true),
+        File::with_name(&env_config_name).required(true),
+        environment_variables(),
+    );
+
+    load_with(&[&base_config_name, &env_config_name], builder, deserialize)
+}
+
+/// Load config by path from selected [Environment] like [load_config_by_path], with an explicit
+/// [FileFormat] for both base and environment config files instead of detecting it from extension.
+/// Use this when config files of multiple formats exist side by side, ie. during migration from
+/// `develop.yaml` to `develop.toml`.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_config_by_path_with_format;
+/// # use avantis_utils::config::Environment;
+/// # use avantis_utils::config::FileFormat;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let config: MyConfig =
+///         load_config_by_path_with_format(Environment::Develop, "config", FileFormat::Yaml).unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_config_by_path_with_format<'de, T: Deserialize<'de>>(
+    environment: Environment,
+    path: &str,
+    format: FileFormat,
+) -> Result<T> {
+    let [base_config_name, env_config_name] = config_file_names(path, environment);
+    let builder = config_builder(
+        File::new(&base_config_name, format).required(true),
+        File::new(&env_config_name, format).required(true),
+        environment_variables(),
+    );
+
+    load_with(&[&base_config_name, &env_config_name], builder, deserialize)
+}
+
+/// Load config from a single `file` with sections per environment. The `[default]` section is
+/// merged with the section named after selected [Environment] (ie. `[develop]`), then env
+/// overrides are applied like [load_config]. Missing sections are treated as empty.
+/// Returns a Result containing config struct.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_config_sectioned;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     // config/app.toml
+///     //
+///     // [default]
+///     // log_level = "info"
+///     //
+///     // [develop]
+///     // log_level = "debug"
+///     let config: MyConfig = load_config_sectioned(Environment::Develop, "config/app").unwrap();
+///
+///     assert_eq!("debug", config.log_level);
+/// }
+/// ```
+pub fn load_config_sectioned<'de, T: Deserialize<'de>>(
+    environment: Environment,
+    file: &str,
+) -> Result<T> {
+    let builder = Config::builder().add_source(File::with_name(file).required(true));
+
+    load_with(&[file], builder, |config| {
+        deserialize(
+            Config::builder()
+                .add_source(ConfigSection::from_config(&config, DEFAULT_SECTION)?)
+                .add_source(ConfigSection::from_config(
+                    &config,
+                    &environment.to_string(),
+                )?)
+                .add_source(environment_variables())
+                .build()?,
+        )
+    })
+}
+
+const DEFAULT_SECTION: &str = "default";
+
+/// Top level table of a config, used as a source by [load_config_sectioned] and [interpolate].
+#[derive(Clone, Debug)]
+struct ConfigSection(Map<String, Value>);
+
+impl ConfigSection {
+    fn from_config(config: &Config, section: &str) -> Result<Self> {
+        match config.get_table(section) {
+            Ok(table) => Ok(ConfigSection(table)),
+            Err(ConfigError::NotFound(_)) => Ok(ConfigSection(Map::new())),
+            Err(err) => Err(anyhow!("Invalid config section [{}]: {}", section, err)),
+        }
+    }
+}
+
+impl Source for ConfigSection {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> std::result::Result<Map<String, Value>, ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Load config from `base_config_name`, then merge every config file in `dir` on top of it
+/// in lexicographic order of file names, then apply env overrides like [load_config].
+/// Returns a Result containing config struct.
+///
+/// `base_config_name` is without extension like [load_config_by_path], ie. `config/base`.
+/// Both paths are relative to the current directory, not to each other.
+///
+/// Hidden files and subdirectories are skipped, ie. `..data` created by Kubernetes
+/// ConfigMap mounts. Format of each fragment is detected from its extension.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::load_config_from_dir;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let config: MyConfig = load_config_from_dir("config/base", "config/conf.d").unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_config_from_dir<'de, T: Deserialize<'de>>(
+    base_config_name: &str,
+    dir: impl AsRef<Path>,
+) -> Result<T> {
+    let dir = dir.as_ref();
+
+    let builder = config_fragments(dir)?
+        .into_iter()
+        .fold(
+            Config::builder().add_source(File::with_name(base_config_name).required(true)),
+            |builder, fragment| builder.add_source(fragment),
+        )
+        .add_source(environment_variables());
+
+    load_with(
+        &[base_config_name, &dir.to_string_lossy()],
+        builder,
+        deserialize,
+    )
+}
+
+fn config_fragments(dir: &Path) -> Result<Vec<Config>> {
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(|err| anyhow!("Unable to read config dir {}: {}", dir.display(), err))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| path.is_file() && !is_hidden(path));
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            Config::builder()
+                .add_source(File::from(path.as_path()))
+                .build()
+                .map_err(|err| anyhow!("Invalid config fragment {}: {}", path.display(), err))
+        })
+        .collect()
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Load merged config from selected [Environment] with the same sources as [load_config], without
+/// deserializing it. Use this to read keys not in the config struct, ie. feature flags added at
+/// runtime.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::config::load_raw_config;
+/// # use avantis_utils::config::Environment;
+/// let config = load_raw_config(Environment::Develop).unwrap();
+///
+/// let enabled = config.get_bool("features.new_dashboard").unwrap_or(false);
+///
+/// println!("{}", enabled);
+/// ```
+pub fn load_raw_config(environment: Environment) -> Result<Config> {
+    let [base_config_name, env_config_name] = config_file_names(DEFAULT_CONFIG_PATH, environment);
+
+    let builder = config_builder(
+        File::with_name(&base_config_name).required(true),
+        File::with_name(&env_config_name).required(true),
+        environment_variables(),
+    );
+
+    load_with(&[&base_config_name, &env_config_name], builder, Ok)
+}
+
+/// Load config from selected [Environment] like [load_config], then serialize it to pretty JSON.
+/// Fields marked with [Secret] are replaced by `***`.
+///
+/// Useful for debugging which values are in effect after file and env layering.
+///
+/// # Example
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use avantis_utils::config::dump_effective;
+/// # use avantis_utils::config::Environment;
+/// # use avantis_utils::config::Secret;
+/// #[derive(Deserialize, Serialize)]
+/// struct MyConfig {
+///     db: MyDbConfig,
+/// }
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct MyDbConfig {
+///     host: String,
+///     password: Secret<String>,
+/// }
+///
+/// fn main() {
+///     let dumped = dump_effective::<MyConfig>(Environment::Develop).unwrap();
+///
+///     assert!(dumped.contains(r#""password": "***""#));
+/// }
+/// ```
+pub fn dump_effective<T: DeserializeOwned + Serialize>(environment: Environment) -> Result<String> {
+    let config = load_config::<T>(environment)?;
+
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+/// JSON schema of config `T`, ie. to lint config files in CI before deploy. `T` derives
+/// `schemars::JsonSchema`, which is implemented for [Environment], [Secret] and config
+/// structs of other modules, ie. `DatabaseConfig`.
+///
+/// Require `config-schema` feature, and `schemars` 0.8 to derive `JsonSchema`.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use schemars::JsonSchema;
+/// # use avantis_utils::config::schema;
+/// #[derive(Deserialize, JsonSchema)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// let schema = schema::<MyConfig>();
+///
+/// assert_eq!("string", schema["properties"]["log_level"]["type"]);
+/// ```
+#[cfg(feature = "config-schema")]
+pub fn schema<T: schemars::JsonSchema>() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(T)).expect("JSON schema is always serializable")
+}
+
+/// Describe sources layered by [load_config] for selected [Environment], in load order.
+/// Later sources override earlier ones, ie. env variables override environment config file,
+/// which override base config file.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::config::effective_sources;
+/// # use avantis_utils::config::Environment;
+/// for source in effective_sources(Environment::Develop) {
+///     println!("{}", source);
+/// }
+/// ```
+pub fn effective_sources(environment: Environment) -> Vec<SourceDescription> {
+    let [base_config_name, env_config_name] = config_file_names(DEFAULT_CONFIG_PATH, environment);
+
+    vec![
+        SourceDescription::File {
+            name: base_config_name,
+            required: true,
+        },
+        SourceDescription::File {
+            name: env_config_name,
+            required: true,
+        },
+        SourceDescription::EnvironmentVariables {
+            prefix: format!("{}{}", ENV_PREFIX.to_uppercase(), ENV_PREFIX_SEPARATOR),
+            separator: ENV_SEPARATOR.to_string(),
+        },
+    ]
+}
+
+/// Description of a config source. See [effective_sources].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceDescription {
+    /// Config file. Name is without extension, any format listed in [config::FileFormat] is looked up.
+    File { name: String, required: bool },
+    /// Environment variables with prefix and hierarchy separator.
+    EnvironmentVariables { prefix: String, separator: String },
+}
+
+impl std::fmt::Display for SourceDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceDescription::File { name, required } => {
+                write!(f, "file `{}` (required: {})", name, required)
+            }
+            SourceDescription::EnvironmentVariables { prefix, separator } => write!(
+                f,
+                "environment variables with prefix `{}` and separator `{}`",
+                prefix, separator
+            ),
+        }
+    }
+}
+
+const DEFAULT_CONFIG_PATH: &str = "config";
+const DEFAULT_BASE_NAME: &str = "base";
+const ENV_PREFIX: &str = "app";
+const ENV_PREFIX_SEPARATOR: &str = "_";
+const ENV_SEPARATOR: &str = "__";
+const SECRET_FILE_SUFFIX: &str = "_file";
+const LIST_SEPARATOR: char = ',';
+
+fn config_file_names(path: &str, environment: Environment) -> [String; 2] {
+    [
+        format!("{}/{}", path, DEFAULT_BASE_NAME),
+        format!("{}/{}", path, environment),
+    ]
+}
+
+/// Environment variables source used by [load_config] and [load_config_by_path].
+/// Use `APP` for prefix and separator `__` for hierarchy. Prefix is followed by either `_` or
+/// `__`, so `APP_STOCK_DB__DB_NAME` and `APP__STOCK_DB__DB_NAME` both replace
+/// `stock_db.db_name`. If both are set, `APP_` wins.
+///
+/// Env is read when the source is created, not when config is built.
+pub fn environment_variables() -> EnvironmentVariables {
+    EnvironmentVariables::with_prefix(ENV_PREFIX)
+        .prefix_separator(ENV_PREFIX_SEPARATOR)
+        .separator(ENV_SEPARATOR)
+        .source(Some(prefixed_env_vars(std::env::vars())))
+}
+
+/// `vars` with `APP__` prefix rewritten to `APP_`, without overriding vars already
+/// prefixed with `APP_`.
+fn prefixed_env_vars(vars: impl Iterator<Item = (String, String)>) -> Map<String, String> {
+    let mut nested = Map::new();
+    let mut prefixed = Map::new();
+    for (name, value) in vars {
+        match strip_nested_prefix(&name) {
+            Some(name) => nested.insert(name, value),
+            None => prefixed.insert(name, value),
+        };
+    }
+
+    nested.extend(prefixed);
+    nested
+}
+
+/// `APP_STOCK_DB` for `APP__STOCK_DB`, `None` for env without `APP__` prefix.
+fn strip_nested_prefix(name: &str) -> Option<String> {
+    let nested_prefix = format!("{}{}", ENV_PREFIX, ENV_SEPARATOR);
+    let prefix = name.get(..nested_prefix.len())?;
+    if !prefix.eq_ignore_ascii_case(&nested_prefix) {
+        return None;
+    }
+
+    Some(format!(
+        "{}{}{}",
+        &name[..ENV_PREFIX.len()],
+        ENV_PREFIX_SEPARATOR,
+        &name[nested_prefix.len()..]
+    ))
+}
+
+/// Secret files source used by [load_config_with_secret_files], layered after
+/// [environment_variables]. Not part of other loaders, as any env with `_FILE` suffix would be
+/// read as a pointer, ie. `APP_LOG_FILE`. For each env with `APP_` or `APP__` prefix and `_FILE` suffix,
+/// the content of the file it points to replaces config at the field named without the suffix.
+/// Trailing newlines are trimmed. Fail if the file can't be read.
+///
+/// For example, `APP_DB__PASSWORD_FILE=/run/secrets/db_password` replaces `db.password`.
+pub fn secret_files() -> SecretFiles {
+    SecretFiles {
+        prefix: format!("{}{}", ENV_PREFIX, ENV_PREFIX_SEPARATOR),
+        suffix: SECRET_FILE_SUFFIX.to_string(),
+        separator: ENV_SEPARATOR.to_string(),
+    }
+}
+
+/// Config source reading values from files pointed by environment variables. See [secret_files].
+#[derive(Clone, Debug)]
+pub struct SecretFiles {
+    prefix: String,
+    suffix: String,
+    separator: String,
+}
+
+impl SecretFiles {
+    /// Config key of env `name`, if it points to a secret file.
+    fn key(&self, name: &str) -> Option<String> {
+        let name = name.to_lowercase();
+        let key = name.strip_prefix(&self.prefix)?;
+        // `APP__` prefix, like [environment_variables].
+        let key = key
+            .strip_prefix(ENV_PREFIX_SEPARATOR)
+            .unwrap_or(key)
+            .strip_suffix(&self.suffix)?
+            .replace(&self.separator, ".");
+
+        Some(key).filter(|key| !key.is_empty())
+    }
+}
+
+impl Source for SecretFiles {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> std::result::Result<Map<String, Value>, ConfigError> {
+        std::env::vars()
+            .filter_map(|(name, path)| self.key(&name).map(|key| (name, key, path)))
+            .map(|(name, key, path)| {
+                let content = std::fs::read_to_string(&path).map_err(|err| {
+                    ConfigError::Message(format!(
+                        "Unable to read secret file {} of env {}: {}",
+                        path, name, err
+                    ))
+                })?;
+                let origin = format!("file {} of env {}", path, name);
+                let value = content.trim_end_matches(['\r', '\n']).to_string();
+
+                Ok((key, Value::new(Some(&origin), value)))
+            })
+            .collect()
+    }
+}
+
+/// Environment variables source like [environment_variables], with support for list overrides.
+/// Values of keys listed in `list_keys` are split by `,` into a list. Other values remain
+/// strings, ie. `0123` is not parsed into a number. A list from env replaces the whole list
+/// from config files.
+///
+/// Keys use config hierarchy, ie. `redis.hosts` for env `APP_REDIS__HOSTS`.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::environment_variables_with_list_keys;
+/// # use avantis_utils::config::load_custom_config;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     // `APP_REDIS__HOSTS=redis://a:6379,redis://b:6379` will be loaded into a `Vec<String>`.
+///     let config: MyConfig = load_custom_config(
+///         config_rs::File::with_name("config/base"),
+///         config_rs::File::with_name("config/test"),
+///         environment_variables_with_list_keys(&["redis.hosts"]),
+///     ).unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn environment_variables_with_list_keys(list_keys: &[&str]) -> ListEnvironmentVariables {
+    let vars = prefixed_env_vars(std::env::vars());
+    let (vars, lists) = split_list_env_vars(vars, list_keys);
+
+    ListEnvironmentVariables {
+        env_vars: environment_variables().source(Some(vars)),
+        lists,
+    }
+}
+
+/// Environment variables source with list overrides. See [environment_variables_with_list_keys].
+#[derive(Clone, Debug)]
+pub struct ListEnvironmentVariables {
+    env_vars: EnvironmentVariables,
+    lists: Map<String, Value>,
+}
+
+impl Source for ListEnvironmentVariables {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> std::result::Result<Map<String, Value>, ConfigError> {
+        let mut values = self.env_vars.collect()?;
+        values.extend(self.lists.clone());
+
+        Ok(values)
+    }
+}
+
+/// Split `vars` into other envs and values of `list_keys` as lists by config key. A list is
+/// set as a single value, so it replaces the whole list from config files. config-rs only
+/// splits lists when parsing every value.
+fn split_list_env_vars(
+    vars: Map<String, String>,
+    list_keys: &[&str],
+) -> (Map<String, String>, Map<String, Value>) {
+    let prefix = format!("{}{}", ENV_PREFIX, ENV_PREFIX_SEPARATOR);
+    let list_key = |name: &str| {
+        let name = name.to_lowercase();
+        name.strip_prefix(&prefix)
+            .map(|key| key.replace(ENV_SEPARATOR, "."))
+            .filter(|key| {
+                list_keys
+                    .iter()
+                    .any(|list_key| list_key.eq_ignore_ascii_case(key))
+            })
+    };
 
-    let custom_env_vars = EnvironmentVariables::with_prefix("app")
-        .prefix_separator("_")
-        .separator("__");
+    let mut others = Map::new();
+    let mut lists = Map::new();
+    for (name, value) in vars {
+        let key = match list_key(&name) {
+            Some(key) => key,
+            None => {
+                others.insert(name, value);
+                continue;
+            }
+        };
+
+        let origin = format!("env {}", name);
+        let elements = value
+            .split(LIST_SEPARATOR)
+            .map(|element| Value::new(Some(&origin), element))
+            .collect::<Vec<_>>();
+        lists.insert(key, Value::new(Some(&origin), ValueKind::Array(elements)));
+    }
 
-    load_custom_config(base_config_file, env_config_file, custom_env_vars)
+    (others, lists)
 }
 
 /// Load config from custom sources.
@@ -129,27 +832,124 @@ pub fn load_config_by_path<'de, T: Deserialize<'de>>(
 pub fn load_custom_config<'de, T: Deserialize<'de>>(
     base_config_file: File<FileSourceFile, FileFormat>,
     env_config_file: File<FileSourceFile, FileFormat>,
-    custom_env_vars: EnvironmentVariables,
+    custom_env_vars: impl Source + Send + Sync + 'static,
 ) -> Result<T> {
+    let builder = config_builder(base_config_file, env_config_file, custom_env_vars);
+
+    load_with(&[], builder, deserialize)
+}
+
+/// Load config from custom sources like [load_custom_config], expanding `${VAR}` in string
+/// values from env after merging sources. `$$` is an escaped `$`. See [interpolate].
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::{load_custom_config_interpolated, UnknownVariable};
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let config: MyConfig = load_custom_config_interpolated(
+///         config_rs::File::with_name("config/base"),
+///         config_rs::File::with_name("config/test"),
+///         config_rs::Environment::with_prefix("app").separator("__"),
+///         UnknownVariable::Error,
+///     ).unwrap();
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+pub fn load_custom_config_interpolated<'de, T: Deserialize<'de>>(
+    base_config_file: File<FileSourceFile, FileFormat>,
+    env_config_file: File<FileSourceFile, FileFormat>,
+    custom_env_vars: impl Source + Send + Sync + 'static,
+    unknown: UnknownVariable,
+) -> Result<T> {
+    let builder = config_builder(base_config_file, env_config_file, custom_env_vars);
+
+    load_with(&[], builder, |config| {
+        deserialize(interpolate(&config, unknown)?)
+    })
+}
+
+fn config_builder(
+    base_config_file: File<FileSourceFile, FileFormat>,
+    env_config_file: File<FileSourceFile, FileFormat>,
+    custom_env_vars: impl Source + Send + Sync + 'static,
+) -> ConfigBuilder<DefaultState> {
     Config::builder()
         .add_source(base_config_file)
         .add_source(env_config_file)
         .add_source(custom_env_vars)
-        .build()?
+}
+
+/// Build config from `builder` and turn it into `R` with `load`, ie. [deserialize]. Used by every
+/// loader, so each load is a single `config::load` span with resolved config `files`, if known,
+/// and logs elapsed time.
+#[instrument(
+    skip_all,
+    name = "config::load",
+    fields(files = ?files.iter().map(|file| resolve_config_file(file)).collect::<Vec<_>>())
+)]
+fn load_with<R>(
+    files: &[&str],
+    builder: ConfigBuilder<DefaultState>,
+    load: impl FnOnce(Config) -> Result<R>,
+) -> Result<R> {
+    let started_at = Instant::now();
+
+    let loaded = load(builder.build()?)?;
+
+    info!(
+        elapsed_ms = started_at.elapsed().as_millis() as u64,
+        "loaded config with type {}",
+        std::any::type_name::<R>()
+    );
+
+    Ok(loaded)
+}
+
+fn deserialize<'de, T: Deserialize<'de>>(config: Config) -> Result<T> {
+    config
         .try_deserialize()
-        .map_err(|err| {
-            anyhow!(
-                "Unable to deserialize into config with type {} with error: {}",
-                std::any::type_name::<T>(),
-                err
-            )
-        })
+        .map_err(|err| ConfigDeserializeError::new::<T>(err).into())
 }
 
+/// File config-rs loads for `name` without extension, ie. `config/develop.yaml` for
+/// `config/develop`. Return `name` itself if no file matches.
+fn resolve_config_file(name: &str) -> String {
+    if Path::new(name).is_file() {
+        return name.to_string();
+    }
+
+    CONFIG_FILE_FORMATS
+        .iter()
+        .flat_map(|format| format.file_extensions())
+        .map(|extension| format!("{}.{}", name, extension))
+        .find(|file| Path::new(file).is_file())
+        .unwrap_or_else(|| name.to_string())
+}
+
+const CONFIG_FILE_FORMATS: [FileFormat; 6] = [
+    FileFormat::Toml,
+    FileFormat::Json,
+    FileFormat::Yaml,
+    FileFormat::Ini,
+    FileFormat::Ron,
+    FileFormat::Json5,
+];
+
 /// Application environment. Affect configuration file loaded by [load_config].
 ///
 /// Any format listed in [config::FileFormat] can be used.
-#[derive(PartialEq, Eq, Debug, EnumString, strum::Display)]
+///
+/// Can be used as a config field, (de)serialized from its name, ie. `"develop"`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, EnumString, strum::Display, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum Environment {
     /// Local environment. Will use `config/local.[FORMAT]`.
     #[strum(serialize = "local")]
@@ -195,12 +995,75 @@ impl Environment {
     /// ```
     pub fn from_custom_env(key: &str) -> Result<Self> {
         std::env::var(key)
-            .map(|environment_string| {
-                Environment::from_str(&environment_string)
-                    .map_err(|_| anyhow!("Unknown environment: {environment_string}"))
-            })
+            .map(Environment::try_from)
             .unwrap_or_else(|_| Ok(Environment::default()))
     }
+
+    /// Returns all environments. Useful for iterating every environment, ex. in tests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::config::Environment;
+    /// for environment in Environment::all() {
+    ///     println!("{}", environment);
+    /// }
+    /// ```
+    pub fn all() -> &'static [Environment] {
+        &[
+            Environment::Local,
+            Environment::Test,
+            Environment::Develop,
+            Environment::Production,
+        ]
+    }
+
+    /// Returns `true` if environment is [Environment::Production].
+    pub fn is_production(&self) -> bool {
+        matches!(self, Environment::Production)
+    }
+
+    /// Returns `true` if environment is [Environment::Local].
+    pub fn is_local(&self) -> bool {
+        matches!(self, Environment::Local)
+    }
+}
+
+impl TryFrom<String> for Environment {
+    type Error = anyhow::Error;
+
+    fn try_from(environment_string: String) -> Result<Self> {
+        Environment::from_str(&environment_string)
+            .map_err(|_| anyhow!("Unknown environment: {environment_string}"))
+    }
+}
+
+impl From<Environment> for String {
+    fn from(environment: Environment) -> Self {
+        environment.to_string()
+    }
+}
+
+/// String schema of environment names, ie. `"develop"`.
+#[cfg(feature = "config-schema")]
+impl schemars::JsonSchema for Environment {
+    fn schema_name() -> String {
+        "Environment".to_string()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            enum_values: Some(
+                Environment::all()
+                    .iter()
+                    .map(|environment| environment.to_string().into())
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+        .into()
+    }
 }
 
 impl Default for Environment {
@@ -276,6 +1139,17 @@ mod tests {
         std::env::remove_var("APP_DB__PASSWORD");
     }
 
+    #[test]
+    fn test_resolve_config_file() {
+        assert_eq!("config/develop.yaml", resolve_config_file("config/develop"));
+        assert_eq!("config/test.toml", resolve_config_file("config/test"));
+        assert_eq!(
+            "config/production.json",
+            resolve_config_file("config/production.json")
+        );
+        assert_eq!("config/missing", resolve_config_file("config/missing"));
+    }
+
     #[test]
     #[serial]
     fn test_load_config_by_path_success() {
@@ -323,6 +1197,245 @@ mod tests {
         std::env::remove_var("APP_DB__PASSWORD");
     }
 
+    #[test]
+    #[serial]
+    fn test_load_custom_config_files() {
+        let dir = std::env::temp_dir().join(format!("avantis-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("common.yaml"), "log_level: info\nname: my-service").unwrap();
+        std::fs::write(dir.join("develop.yaml"), "log_level: debug").unwrap();
+        let path = dir.to_string_lossy();
+
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyServiceConfig {
+            log_level: String,
+            name: String,
+        }
+
+        let actual = load_custom_config_files::<MyServiceConfig>("common", "develop", &path);
+        let missing_base = load_custom_config_files::<MyServiceConfig>("base", "develop", &path);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            MyServiceConfig {
+                log_level: "debug".to_string(),
+                name: "my-service".to_string(),
+            },
+            actual.unwrap()
+        );
+        assert!(missing_base.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_by_path_with_format() {
+        let dir = std::env::temp_dir().join(format!("avantis-format-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base.yaml"), "log_level: info").unwrap();
+        std::fs::write(dir.join("base.toml"), "log_level = \"info\"").unwrap();
+        std::fs::write(dir.join("develop.yaml"), "log_level: debug").unwrap();
+        std::fs::write(dir.join("develop.toml"), "log_level = \"warn\"").unwrap();
+        let path = dir.to_string_lossy();
+
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyLogConfig {
+            log_level: String,
+        }
+
+        let yaml = load_config_by_path_with_format::<MyLogConfig>(
+            Environment::Develop,
+            &path,
+            FileFormat::Yaml,
+        );
+        let toml = load_config_by_path_with_format::<MyLogConfig>(
+            Environment::Develop,
+            &path,
+            FileFormat::Toml,
+        );
+        let json = load_config_by_path_with_format::<MyLogConfig>(
+            Environment::Develop,
+            &path,
+            FileFormat::Json,
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("debug", yaml.unwrap().log_level);
+        assert_eq!("warn", toml.unwrap().log_level);
+        assert!(json.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_sectioned() {
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+
+        let expected = |log_level: &str, host: &str, max_connections| MyConfig {
+            log_level: log_level.to_string(),
+            db: MyDbConfig {
+                host: host.to_string(),
+                user: "username".to_string(),
+                password: "supersecurepassword".to_string(),
+                db_name: "my_db".to_string(),
+                max_connections,
+            },
+        };
+
+        let develop = load_config_sectioned::<MyConfig>(Environment::Develop, "config/app");
+        let production = load_config_sectioned::<MyConfig>(Environment::Production, "config/app");
+        let test = load_config_sectioned::<MyConfig>(Environment::Test, "config/app");
+
+        std::env::remove_var("APP_DB__PASSWORD");
+
+        assert_eq!(
+            expected("debug", "db.develop.internal", 30),
+            develop.unwrap()
+        );
+        assert_eq!(
+            expected("info", "db.production.internal", 100),
+            production.unwrap()
+        );
+        assert_eq!(expected("info", "localhost", 30), test.unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_sectioned_invalid_section() {
+        let err = load_config_sectioned::<MyConfig>(Environment::Develop, "config/base")
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("missing field"), "{}", err);
+
+        let dir = std::env::temp_dir().join(format!("avantis-sectioned-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.toml"), "develop = \"debug\"").unwrap();
+
+        let err = load_config_sectioned::<MyConfig>(
+            Environment::Develop,
+            &dir.join("app").to_string_lossy(),
+        )
+        .unwrap_err();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.to_string().contains("Invalid config section [develop]"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_from_dir() {
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+
+        let expected = MyConfig {
+            log_level: "info".to_string(),
+            db: MyDbConfig {
+                host: "db.internal".to_string(),
+                user: "username".to_string(),
+                password: "supersecurepassword".to_string(),
+                db_name: "my_db".to_string(),
+                max_connections: 10,
+            },
+        };
+
+        let actual = load_config_from_dir::<MyConfig>("config/base", "config/conf.d");
+
+        std::env::remove_var("APP_DB__PASSWORD");
+
+        assert_eq!(expected, actual.unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_from_dir_bad_fragment() {
+        let dir = std::env::temp_dir().join(format!("avantis-conf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("10-good.toml"), "log_level = \"debug\"").unwrap();
+        std::fs::write(dir.join("20-bad.toml"), "log_level = [").unwrap();
+
+        let err = load_config_from_dir::<MyConfig>("config/base", &dir).unwrap_err();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.to_string().contains("Invalid config fragment"));
+        assert!(err.to_string().contains("20-bad.toml"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_with_list_keys() {
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyListConfig {
+            log_level: String,
+            redis: MyRedisConfig,
+        }
+
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyRedisConfig {
+            hosts: Vec<String>,
+            max_connections: u32,
+        }
+
+        std::env::set_var("APP_REDIS__HOSTS", "redis://a:6379,redis://b:6379");
+        std::env::set_var("APP_LOG_LEVEL", "debug");
+
+        let expected = MyListConfig {
+            log_level: "debug".to_string(),
+            redis: MyRedisConfig {
+                hosts: vec!["redis://a:6379".to_string(), "redis://b:6379".to_string()],
+                max_connections: 2,
+            },
+        };
+
+        let actual = load_custom_config::<MyListConfig>(
+            File::with_name("config/base").required(true),
+            File::with_name("config/develop").required(true),
+            environment_variables_with_list_keys(&["redis.hosts"]),
+        )
+        .unwrap();
+
+        std::env::remove_var("APP_REDIS__HOSTS");
+        std::env::remove_var("APP_LOG_LEVEL");
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_with_list_keys_keeps_strings() {
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyAccountConfig {
+            account_no: String,
+            fee_rate: String,
+            redis: MyRedisConfig,
+        }
+
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyRedisConfig {
+            hosts: Vec<String>,
+        }
+
+        std::env::set_var("APP_ACCOUNT_NO", "0123");
+        std::env::set_var("APP_FEE_RATE", "1.50");
+        std::env::set_var("APP_REDIS__HOSTS", "redis://a:6379");
+
+        let actual = load_custom_config::<MyAccountConfig>(
+            File::with_name("config/base").required(true),
+            File::with_name("config/develop").required(true),
+            environment_variables_with_list_keys(&["redis.hosts"]),
+        );
+
+        std::env::remove_var("APP_ACCOUNT_NO");
+        std::env::remove_var("APP_FEE_RATE");
+        std::env::remove_var("APP_REDIS__HOSTS");
+
+        let actual = actual.unwrap();
+        assert_eq!("0123", actual.account_no);
+        assert_eq!("1.50", actual.fee_rate);
+        assert_eq!(vec!["redis://a:6379".to_string()], actual.redis.hosts);
+    }
+
     #[test]
     #[serial]
     #[should_panic(expected = "configuration file \"config/staging\" not found")]
@@ -383,4 +1496,314 @@ mod tests {
 
         result.unwrap();
     }
+
+    #[test]
+    fn test_environment_serde() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct MyEnvironmentConfig {
+            environment: Environment,
+        }
+
+        let config: MyEnvironmentConfig =
+            serde_json::from_str(r#"{"environment":"develop"}"#).unwrap();
+
+        assert_eq!(Environment::Develop, config.environment);
+        assert_eq!(
+            r#"{"environment":"develop"}"#,
+            serde_json::to_string(&config).unwrap()
+        );
+
+        let err = serde_json::from_str::<MyEnvironmentConfig>(r#"{"environment":"staging"}"#)
+            .unwrap_err();
+
+        assert!(err.to_string().starts_with("Unknown environment: staging"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_or_default() {
+        #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+        struct MyDefaultConfig {
+            log_level: String,
+            missing_field: String,
+        }
+
+        let actual = load_config_or_default::<MyDefaultConfig>(Environment::Develop);
+
+        assert_eq!(MyDefaultConfig::default(), actual);
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_raw_config() {
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+        std::env::set_var("APP_FEATURES__NEW_DASHBOARD", "true");
+
+        let config = load_raw_config(Environment::Develop);
+
+        std::env::remove_var("APP_DB__PASSWORD");
+        std::env::remove_var("APP_FEATURES__NEW_DASHBOARD");
+
+        let config = config.unwrap();
+        assert_eq!("info", config.get_string("log_level").unwrap());
+        assert_eq!(30, config.get_int("db.max_connections").unwrap());
+        assert_eq!(
+            "supersecurepassword",
+            config.get_string("db.password").unwrap()
+        );
+        assert!(config.get_bool("features.new_dashboard").unwrap());
+        assert!(config.get_string("features.missing").is_err());
+
+        let actual: MyConfig = config.try_deserialize().unwrap();
+        assert_eq!("supersecurepassword", actual.db.password);
+    }
+
+    #[test]
+    #[serial]
+    fn test_dump_effective() {
+        #[derive(Deserialize, Serialize)]
+        struct MySecretConfig {
+            log_level: String,
+            db: MySecretDbConfig,
+        }
+
+        #[derive(Deserialize, Serialize)]
+        struct MySecretDbConfig {
+            host: String,
+            password: Secret<String>,
+        }
+
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+
+        let actual = dump_effective::<MySecretConfig>(Environment::Develop).unwrap();
+
+        std::env::remove_var("APP_DB__PASSWORD");
+
+        let expected = r#"{
+  "log_level": "info",
+  "db": {
+    "host": "localhost",
+    "password": "***"
+  }
+}"#;
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_effective_sources() {
+        assert_eq!(
+            vec![
+                SourceDescription::File {
+                    name: "config/base".to_string(),
+                    required: true,
+                },
+                SourceDescription::File {
+                    name: "config/develop".to_string(),
+                    required: true,
+                },
+                SourceDescription::EnvironmentVariables {
+                    prefix: "APP_".to_string(),
+                    separator: "__".to_string(),
+                },
+            ],
+            effective_sources(Environment::Develop)
+        );
+    }
+
+    #[test]
+    fn test_secret_files_key() {
+        let source = secret_files();
+
+        assert_eq!(
+            Some("db.password".to_string()),
+            source.key("APP_DB__PASSWORD_FILE")
+        );
+        assert_eq!(None, source.key("APP_DB__PASSWORD"));
+        assert_eq!(None, source.key("OTHER_DB__PASSWORD_FILE"));
+        assert_eq!(None, source.key("APP__FILE"));
+
+        assert_eq!(
+            Some("stock_db.password".to_string()),
+            source.key("APP__STOCK_DB__PASSWORD_FILE")
+        );
+    }
+
+    #[test]
+    fn test_strip_nested_prefix() {
+        assert_eq!(
+            Some("APP_STOCK_DB__PASSWORD".to_string()),
+            strip_nested_prefix("APP__STOCK_DB__PASSWORD")
+        );
+        assert_eq!(
+            Some("app_stock_db__password".to_string()),
+            strip_nested_prefix("app__stock_db__password")
+        );
+        assert_eq!(None, strip_nested_prefix("APP_STOCK_DB__PASSWORD"));
+        assert_eq!(None, strip_nested_prefix("APPLICATION__NAME"));
+        assert_eq!(None, strip_nested_prefix("AP"));
+    }
+
+    #[test]
+    fn test_environment_variables_with_underscores() {
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyAppConfig {
+            log_level: String,
+            stock_db: MyStockDbConfig,
+        }
+
+        #[derive(Clone, Debug, Deserialize, PartialEq)]
+        struct MyStockDbConfig {
+            db_name: String,
+            max_connections: u32,
+            password: String,
+        }
+
+        let vars = [
+            ("APP_LOG_LEVEL", "debug"),
+            ("APP__STOCK_DB__DB_NAME", "stock"),
+            ("APP_STOCK_DB__MAX_CONNECTIONS", "10"),
+            ("APP__STOCK_DB__PASSWORD", "ignored"),
+            ("APP_STOCK_DB__PASSWORD", "supersecurepassword"),
+            ("OTHER__STOCK_DB__DB_NAME", "other"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let source = environment_variables().source(Some(prefixed_env_vars(vars.into_iter())));
+
+        let actual = Config::builder()
+            .add_source(source)
+            .build()
+            .unwrap()
+            .try_deserialize::<MyAppConfig>()
+            .unwrap();
+
+        assert_eq!(
+            MyAppConfig {
+                log_level: "debug".to_string(),
+                stock_db: MyStockDbConfig {
+                    db_name: "stock".to_string(),
+                    max_connections: 10,
+                    password: "supersecurepassword".to_string(),
+                },
+            },
+            actual
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_with_secret_files() {
+        let path = std::env::temp_dir().join("avantis_utils_test_db_password");
+        std::fs::write(&path, "secretfromfile\n").unwrap();
+
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+        std::env::set_var("APP_DB__PASSWORD_FILE", &path);
+
+        let actual = load_config_with_secret_files::<MyConfig>(Environment::Develop);
+
+        std::env::set_var("APP_DB__PASSWORD_FILE", path.with_extension("missing"));
+
+        let missing = load_config_with_secret_files::<MyConfig>(Environment::Develop);
+
+        std::env::remove_var("APP_DB__PASSWORD");
+        std::env::remove_var("APP_DB__PASSWORD_FILE");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("secretfromfile", actual.unwrap().db.password);
+        assert!(missing
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("Unable to read secret file"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_config_ignores_file_envs() {
+        let path = std::env::temp_dir().join("avantis_utils_test_db_host");
+        std::fs::write(&path, "hostfromfile\n").unwrap();
+
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+        std::env::set_var("APP_DB__HOST_FILE", &path);
+        std::env::set_var("APP_LOG_FILE", path.with_extension("missing"));
+
+        let actual = load_config::<MyConfig>(Environment::Develop);
+
+        std::env::remove_var("APP_DB__PASSWORD");
+        std::env::remove_var("APP_DB__HOST_FILE");
+        std::env::remove_var("APP_LOG_FILE");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("localhost", actual.unwrap().db.host);
+    }
+
+    #[test]
+    #[serial]
+    fn test_effective_sources_precedence() {
+        // base.yaml set `log_level`, develop.yaml set `db.host` and env override both.
+        let before = load_config::<MyConfig>(Environment::Develop);
+
+        std::env::set_var("APP_LOG_LEVEL", "debug");
+        std::env::set_var("APP_DB__HOST", "remotehost");
+        std::env::set_var("APP_DB__PASSWORD", "supersecurepassword");
+
+        let actual = load_config::<MyConfig>(Environment::Develop).unwrap();
+
+        std::env::remove_var("APP_LOG_LEVEL");
+        std::env::remove_var("APP_DB__HOST");
+        std::env::remove_var("APP_DB__PASSWORD");
+
+        assert_eq!("REPLACE_ME", before.unwrap().db.password);
+        assert_eq!("debug", actual.log_level);
+        assert_eq!("remotehost", actual.db.host);
+        assert_eq!("supersecurepassword", actual.db.password);
+    }
+
+    #[test]
+    fn test_environment_all() {
+        assert_eq!(
+            &[
+                Environment::Local,
+                Environment::Test,
+                Environment::Develop,
+                Environment::Production,
+            ],
+            Environment::all()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "config-schema")]
+    fn test_schema() {
+        #[derive(Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct MyConfig {
+            environment: Environment,
+            password: Secret<String>,
+            #[serde(default)]
+            replicas: Option<u32>,
+        }
+
+        let schema = schema::<MyConfig>();
+
+        assert_eq!("MyConfig", schema["title"]);
+        assert_eq!(
+            serde_json::json!(["environment", "password"]),
+            schema["required"]
+        );
+        assert_eq!("string", schema["properties"]["password"]["type"]);
+        assert_eq!(
+            serde_json::json!(["local", "test", "develop", "production"]),
+            schema["definitions"]["Environment"]["enum"]
+        );
+    }
+
+    #[test]
+    fn test_environment_predicates() {
+        assert!(Environment::Production.is_production());
+        assert!(!Environment::Develop.is_production());
+        assert!(Environment::Local.is_local());
+        assert!(!Environment::Test.is_local());
+    }
 }
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Env holding the base64 encoded 256-bit key of [EncryptedString] values.
+pub const CONFIG_KEY_ENV: &str = "APP_CONFIG_KEY";
+
+const ENCRYPTED_PREFIX: &str = "encrypted:";
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+const REDACTED: &str = "***";
+
+/// Config value stored encrypted in config files, decrypted on deserialization with the key
+/// of env [CONFIG_KEY_ENV]. The key never comes from config files. Deserialization fails if
+/// the env is missing, the value is not encrypted or can't be decrypted.
+///
+/// Encrypted values are `encrypted:` followed by base64 of nonce and AES-256-GCM ciphertext,
+/// produced by [encrypt_value]. Like [Secret](super::Secret), `Debug` and `Serialize`
+/// output `***`.
+///
+/// Require `config-encryption` feature.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::{encrypt_value, EncryptedString, CONFIG_KEY_ENV};
+/// #[derive(Debug, Deserialize)]
+/// struct DbConfig {
+///     password: EncryptedString,
+/// }
+///
+/// // base64 of 32 bytes key, ie. from `openssl rand -base64 32`
+/// let key = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+/// std::env::set_var(CONFIG_KEY_ENV, key);
+///
+/// let encrypted = encrypt_value("supersecurepassword", key).unwrap();
+/// let config: DbConfig =
+///     serde_json::from_value(serde_json::json!({ "password": encrypted })).unwrap();
+///
+/// assert_eq!("supersecurepassword", config.password.expose());
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptedString(String);
+
+impl EncryptedString {
+    /// Access the decrypted value.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Debug for EncryptedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for EncryptedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptedString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let key = std::env::var(CONFIG_KEY_ENV).map_err(|_| {
+            D::Error::custom(format!(
+                "env {} is required to decrypt config values",
+                CONFIG_KEY_ENV
+            ))
+        })?;
+
+        decrypt_value(&value, &key)
+            .map(EncryptedString)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// String schema, as encrypted values are strings in config files.
+#[cfg(feature = "config-schema")]
+impl schemars::JsonSchema for EncryptedString {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
+/// Encrypt `plaintext` with base64 encoded 256-bit `key`, into a value for [EncryptedString].
+///
+/// Require `config-encryption` feature.
+pub fn encrypt_value(plaintext: &str, key: &str) -> Result<String> {
+    let cipher = cipher(key)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow!("Unable to encrypt config value"))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload)))
+}
+
+/// Decrypt `value` produced by [encrypt_value] with base64 encoded 256-bit `key`.
+///
+/// Require `config-encryption` feature.
+pub fn decrypt_value(value: &str, key: &str) -> Result<String> {
+    let encoded = value.strip_prefix(ENCRYPTED_PREFIX).ok_or_else(|| {
+        anyhow!(
+            "Encrypted config value must start with `{}`",
+            ENCRYPTED_PREFIX
+        )
+    })?;
+    let payload = STANDARD
+        .decode(encoded)
+        .map_err(|err| anyhow!("Invalid encrypted config value: {}", err))?;
+    if payload.len() < NONCE_LENGTH {
+        return Err(anyhow!("Invalid encrypted config value: too short"));
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+    let plaintext = cipher(key)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Unable to decrypt config value, check {}", CONFIG_KEY_ENV))?;
+
+    String::from_utf8(plaintext)
+        .map_err(|err| anyhow!("Decrypted config value is not utf-8: {}", err))
+}
+
+fn cipher(key: &str) -> Result<Aes256Gcm> {
+    let key = STANDARD
+        .decode(key.trim())
+        .map_err(|err| anyhow!("Invalid config key, expect base64: {}", err))?;
+    if key.len() != KEY_LENGTH {
+        return Err(anyhow!(
+            "Invalid config key, expect {} bytes but got {}",
+            KEY_LENGTH,
+            key.len()
+        ));
+    }
+
+    Aes256Gcm::new_from_slice(&key).map_err(|err| anyhow!("Invalid config key: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+
+    const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+    const OTHER_KEY: &str = "ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=";
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let encrypted = encrypt_value("supersecurepassword", KEY).unwrap();
+
+        assert!(encrypted.starts_with("encrypted:"));
+        assert!(!encrypted.contains("supersecurepassword"));
+        assert_ne!(
+            encrypted,
+            encrypt_value("supersecurepassword", KEY).unwrap()
+        );
+        assert_eq!(
+            "supersecurepassword",
+            decrypt_value(&encrypted, KEY).unwrap()
+        );
+
+        assert!(decrypt_value(&encrypted, OTHER_KEY)
+            .unwrap_err()
+            .to_string()
+            .contains("Unable to decrypt config value"));
+        assert!(decrypt_value("supersecurepassword", KEY)
+            .unwrap_err()
+            .to_string()
+            .contains("must start with `encrypted:`"));
+        assert!(decrypt_value("encrypted:AAAA", KEY)
+            .unwrap_err()
+            .to_string()
+            .contains("too short"));
+        assert!(encrypt_value("supersecurepassword", "c2hvcnQ=")
+            .unwrap_err()
+            .to_string()
+            .contains("expect 32 bytes but got 5"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_deserialize() {
+        let encrypted = encrypt_value("supersecurepassword", KEY).unwrap();
+        let previous_key = std::env::var(CONFIG_KEY_ENV);
+
+        std::env::remove_var(CONFIG_KEY_ENV);
+        let missing_key = serde_json::from_value::<EncryptedString>(encrypted.clone().into());
+
+        std::env::set_var(CONFIG_KEY_ENV, KEY);
+        let decrypted = serde_json::from_value::<EncryptedString>(encrypted.into());
+
+        match previous_key {
+            Ok(key) => std::env::set_var(CONFIG_KEY_ENV, key),
+            Err(_) => std::env::remove_var(CONFIG_KEY_ENV),
+        }
+
+        assert!(missing_key
+            .unwrap_err()
+            .to_string()
+            .contains("env APP_CONFIG_KEY is required"));
+
+        let decrypted = decrypted.unwrap();
+        assert_eq!("supersecurepassword", decrypted.expose());
+        assert_eq!("***", format!("{:?}", decrypted));
+        assert_eq!(r#""***""#, serde_json::to_string(&decrypted).unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use config_rs::ConfigError;
+
+/// Error of loading config when it can't be deserialized into the config type, with path of
+/// the failing field if known. Returned wrapped in [anyhow::Error], use
+/// [anyhow::Error::downcast_ref] to access it.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::{load_custom_config, ConfigDeserializeError};
+/// #[derive(Debug, Deserialize)]
+/// struct MyConfig {
+///     log_level: u32,
+/// }
+///
+/// let err = load_custom_config::<MyConfig>(
+///     config_rs::File::with_name("config/base"),
+///     config_rs::File::with_name("config/test"),
+///     config_rs::Environment::with_prefix("app").separator("__"),
+/// )
+/// .unwrap_err();
+///
+/// let err = err.downcast_ref::<ConfigDeserializeError>().unwrap();
+/// assert_eq!(Some("log_level"), err.path.as_deref());
+/// ```
+#[derive(Debug)]
+pub struct ConfigDeserializeError {
+    pub type_name: &'static str,
+    /// Path of the failing field, ie. `db.max_connections` or `redis.hosts[0]`. Errors raised
+    /// by the type itself, ie. a missing field, only tell the field name.
+    pub path: Option<String>,
+    pub source: ConfigError,
+}
+
+impl ConfigDeserializeError {
+    pub(super) fn new<T>(source: ConfigError) -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            path: field_path(&source),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ConfigDeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = self.source.to_string();
+        write!(
+            f,
+            "Unable to deserialize into config with type {} with error: {}",
+            self.type_name, message
+        )?;
+
+        match &self.path {
+            Some(path) if !message.contains(path.as_str()) => write!(f, " at `{}`", path),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigDeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Path of the field `err` is about. config-rs tracks it for type errors, otherwise only the
+/// field name is found in serde messages, ie. "missing field `password`".
+fn field_path(err: &ConfigError) -> Option<String> {
+    match err {
+        ConfigError::Type { key: Some(key), .. } | ConfigError::NotFound(key) => {
+            Some(normalize_path(key))
+        }
+        ConfigError::Message(message) => {
+            let (_, field) = message.split_once("field `")?;
+            let (field, _) = field.split_once('`')?;
+            Some(field.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// config-rs omits the dot after list indexes, ie. `hosts[0]name` for `hosts[0].name`.
+fn normalize_path(key: &str) -> String {
+    let mut path = String::with_capacity(key.len());
+    let mut chars = key.chars().peekable();
+    while let Some(c) = chars.next() {
+        path.push(c);
+        if c == ']'
+            && chars
+                .peek()
+                .is_some_and(|next| *next != '[' && *next != '.')
+        {
+            path.push('.');
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use config_rs::{Config, File, FileFormat};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct MyConfig {
+        db: MyDbConfig,
+        replicas: Vec<MyDbConfig>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct MyDbConfig {
+        host: String,
+        max_connections: u32,
+    }
+
+    fn deserialize_error(yaml: &str) -> ConfigDeserializeError {
+        let err = Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize::<MyConfig>()
+            .unwrap_err();
+
+        ConfigDeserializeError::new::<MyConfig>(err)
+    }
+
+    #[test]
+    fn test_field_path() {
+        let err = deserialize_error("db: {host: localhost, max_connections: many}\nreplicas: []");
+        assert_eq!(Some("db.max_connections"), err.path.as_deref());
+        assert_eq!(
+            "Unable to deserialize into config with type avantis_utils::config::error::tests::MyConfig \
+                with error: invalid type: string \"many\", expected an integer for key `db.max_connections`",
+            err.to_string()
+        );
+
+        let err = deserialize_error(
+            "db: {host: localhost, max_connections: 1}\nreplicas: [{host: replica, max_connections: -1}]",
+        );
+        assert_eq!(Some("replicas[0].max_connections"), err.path.as_deref());
+        assert!(err
+            .to_string()
+            .ends_with("for key `replicas[0]max_connections` at `replicas[0].max_connections`"));
+
+        let err = deserialize_error("db: {max_connections: 1}\nreplicas: []");
+        assert_eq!(Some("host"), err.path.as_deref());
+        assert!(err
+            .to_string()
+            .ends_with("with error: missing field `host`"));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!("db.max_connections", normalize_path("db.max_connections"));
+        assert_eq!("hosts[0]", normalize_path("hosts[0]"));
+        assert_eq!("hosts[0][1].name", normalize_path("hosts[0][1]name"));
+        assert_eq!("hosts[0].name", normalize_path("hosts[0].name"));
+    }
+}
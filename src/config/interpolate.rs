@@ -0,0 +1,224 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use config_rs::{Config, Map, Source, Value, ValueKind};
+
+use super::ConfigSection;
+
+/// What to do with `${VAR}` when `VAR` is not set. See [interpolate].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownVariable {
+    /// Leave `${VAR}` as is.
+    Keep,
+    /// Fail loading config.
+    Error,
+}
+
+/// Expand `${VAR}` in every string value of `config` from process env. `$$` is an escaped `$`.
+/// Run it on merged config, before deserialization, so values from every source are expanded.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::config::{interpolate, UnknownVariable};
+/// # use config_rs::{Config, File, FileFormat};
+/// std::env::set_var("REGION", "ap-southeast-1");
+///
+/// let config = Config::builder()
+///     .add_source(File::from_str(
+///         r#"endpoint = "https://${REGION}.api.internal/$$metrics""#,
+///         FileFormat::Toml,
+///     ))
+///     .build()
+///     .unwrap();
+///
+/// let config = interpolate(&config, UnknownVariable::Error).unwrap();
+///
+/// assert_eq!(
+///     "https://ap-southeast-1.api.internal/$metrics",
+///     config.get_string("endpoint").unwrap()
+/// );
+/// ```
+pub fn interpolate(config: &Config, unknown: UnknownVariable) -> Result<Config> {
+    interpolate_with(config, unknown, |name| std::env::var(name).ok())
+}
+
+fn interpolate_with<F>(config: &Config, unknown: UnknownVariable, lookup: F) -> Result<Config>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut table = config.collect()?;
+    interpolate_table("", &mut table, unknown, &lookup)?;
+
+    Ok(Config::builder().add_source(ConfigSection(table)).build()?)
+}
+
+fn interpolate_table<F>(
+    path: &str,
+    table: &mut Map<String, Value>,
+    unknown: UnknownVariable,
+    lookup: &F,
+) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for (key, value) in table.iter_mut() {
+        let path = match path {
+            "" => key.clone(),
+            _ => format!("{}.{}", path, key),
+        };
+        interpolate_value(&path, value, unknown, lookup)?;
+    }
+    Ok(())
+}
+
+fn interpolate_value<F>(
+    path: &str,
+    value: &mut Value,
+    unknown: UnknownVariable,
+    lookup: &F,
+) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match &mut value.kind {
+        ValueKind::String(string) => {
+            *string = interpolate_str(string, unknown, lookup)
+                .map_err(|err| anyhow!("Unable to interpolate config at `{}`: {}", path, err))?;
+        }
+        ValueKind::Table(table) => interpolate_table(path, table, unknown, lookup)?,
+        ValueKind::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                interpolate_value(&format!("{}[{}]", path, index), value, unknown, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str<F>(value: &str, unknown: UnknownVariable, lookup: &F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed `${{` in `{}`", value))?;
+            let name = &after[..end];
+            match (lookup(name), unknown) {
+                (Some(var), _) => result.push_str(&var),
+                (None, UnknownVariable::Keep) => result.push_str(&rest[..end + 3]),
+                (None, UnknownVariable::Error) => {
+                    return Err(anyhow!("environment variable `{}` is not set", name))
+                }
+            }
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use config_rs::{File, FileFormat};
+
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "REGION" => Some("ap-southeast-1".to_string()),
+            "PORT" => Some("8080".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate_str() {
+        let interpolate = |value| interpolate_str(value, UnknownVariable::Keep, &lookup).unwrap();
+
+        assert_eq!("plain", interpolate("plain"));
+        assert_eq!(
+            "https://ap-southeast-1.api.internal:8080",
+            interpolate("https://${REGION}.api.internal:${PORT}")
+        );
+        assert_eq!("${REGION}", interpolate("$${REGION}"));
+        assert_eq!("$ap-southeast-1", interpolate("$$${REGION}"));
+        assert_eq!("cost $5", interpolate("cost $5"));
+        assert_eq!(
+            "${MISSING}/ap-southeast-1",
+            interpolate("${MISSING}/${REGION}")
+        );
+
+        let err = interpolate_str("${MISSING}", UnknownVariable::Error, &lookup).unwrap_err();
+        assert_eq!("environment variable `MISSING` is not set", err.to_string());
+
+        let err = interpolate_str("${REGION", UnknownVariable::Keep, &lookup).unwrap_err();
+        assert_eq!("unclosed `${` in `${REGION`", err.to_string());
+    }
+
+    #[derive(Debug, serde::Deserialize, PartialEq)]
+    struct MyConfig {
+        endpoint: String,
+        port: u16,
+        replicas: Vec<String>,
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let config = Config::builder()
+            .add_source(File::from_str(
+                r#"
+                endpoint = "https://${REGION}.api.internal"
+                port = "${PORT}"
+                replicas = ["${REGION}-a", "${MISSING}"]
+                "#,
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let config = interpolate_with(&config, UnknownVariable::Keep, lookup).unwrap();
+
+        assert_eq!(
+            MyConfig {
+                endpoint: "https://ap-southeast-1.api.internal".to_string(),
+                port: 8080,
+                replicas: vec!["ap-southeast-1-a".to_string(), "${MISSING}".to_string()],
+            },
+            config.try_deserialize().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_interpolate_unknown_variable_error() {
+        let config = Config::builder()
+            .add_source(File::from_str(
+                r#"db = { hosts = ["localhost", "${MISSING}"] }"#,
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let err = interpolate_with(&config, UnknownVariable::Error, lookup).unwrap_err();
+
+        assert_eq!(
+            "Unable to interpolate config at `db.hosts[1]`: environment variable `MISSING` is not set",
+            err.to_string()
+        );
+    }
+}
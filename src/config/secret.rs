@@ -0,0 +1,99 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const REDACTED: &str = "***";
+
+/// Wrapper marking a config field as secret, ie. passwords or API keys.
+///
+/// Deserialize transparently from the inner value, but `Debug` and `Serialize` output `***`
+/// so the value never leaks into logs or [dump_effective](super::dump_effective) output.
+/// Use [Secret::expose] to read the value.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::Secret;
+/// #[derive(Debug, Deserialize)]
+/// struct DbConfig {
+///     password: Secret<String>,
+/// }
+///
+/// let config: DbConfig = serde_json::from_str(r#"{"password": "supersecurepassword"}"#).unwrap();
+///
+/// assert_eq!("supersecurepassword", config.password.expose());
+/// assert_eq!("DbConfig { password: *** }", format!("{:?}", config));
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Access the secret value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Same schema as the inner value, since it is deserialized transparently.
+#[cfg(feature = "config-schema")]
+impl<T: schemars::JsonSchema> schemars::JsonSchema for Secret<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        T::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        T::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret() {
+        let secret: Secret<String> = serde_json::from_str(r#""password""#).unwrap();
+
+        assert_eq!("password", secret.expose());
+        assert_eq!("***", format!("{:?}", secret));
+        assert_eq!(r#""***""#, serde_json::to_string(&secret).unwrap());
+        assert_eq!("password", secret.into_inner());
+    }
+}
@@ -0,0 +1,186 @@
+//! Test utilities for config. Require `test-util` feature.
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::load_config;
+use super::load_config_by_path;
+use super::Environment;
+
+/// Assert that config of type `T` can be loaded for selected [Environment].
+/// Returns the loaded config struct, panic with a descriptive message otherwise.
+/// Convenience [load_config], designed to be used in downstream crates tests.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::testing::assert_config_loads;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// fn main() {
+///     let config: MyConfig = assert_config_loads(Environment::Develop);
+///
+///     println!("{:?}", config);
+/// }
+/// ```
+#[track_caller]
+pub fn assert_config_loads<'de, T: Deserialize<'de>>(environment: Environment) -> T {
+    load_config(environment).unwrap_or_else(|err| {
+        panic!(
+            "Unable to load config for environment [{}]: {}",
+            environment, err
+        )
+    })
+}
+
+/// Load config of type `T` from `path` for every [Environment] and report all failures,
+/// rather than stopping at the first one. Useful in CI to catch a field added to one config
+/// file but missing in another.
+///
+/// # Example
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use avantis_utils::config::testing::verify_all_environments;
+/// # use avantis_utils::config::Environment;
+/// #[derive(Clone, Debug, Deserialize, PartialEq)]
+/// struct MyConfig {
+///     log_level: String,
+/// }
+///
+/// let report = verify_all_environments::<MyConfig>("config");
+///
+/// // there is no `config/local.[FORMAT]` in this repository.
+/// assert_eq!(vec![Environment::Local], report.failed_environments());
+/// ```
+pub fn verify_all_environments<T: DeserializeOwned>(path: &str) -> VerifyReport {
+    let failures = Environment::all()
+        .iter()
+        .filter_map(|environment| {
+            load_config_by_path::<T>(*environment, path)
+                .err()
+                .map(|err| (*environment, err.to_string()))
+        })
+        .collect();
+
+    VerifyReport {
+        path: path.to_string(),
+        failures,
+    }
+}
+
+/// Result of [verify_all_environments]. Display lists every failed environment with its error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub path: String,
+    /// Failed environments with their error, in [Environment::all] order.
+    pub failures: Vec<(Environment, String)>,
+}
+
+impl VerifyReport {
+    /// Returns `true` if config loads for every environment.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn failed_environments(&self) -> Vec<Environment> {
+        self.failures
+            .iter()
+            .map(|(environment, _)| *environment)
+            .collect()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "config at `{}` loads for all environments", self.path);
+        }
+
+        write!(
+            f,
+            "config at `{}` fails to load for {} environment(s):",
+            self.path,
+            self.failures.len()
+        )?;
+        for (environment, err) in &self.failures {
+            write!(f, "\n  [{}] {}", environment, err)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serial_test::serial;
+
+    use super::*;
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    struct MyLogConfig {
+        log_level: String,
+    }
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    struct MyMissingFieldConfig {
+        log_level: String,
+        missing_field: String,
+    }
+
+    #[test]
+    #[serial]
+    fn test_assert_config_loads() {
+        std::env::set_var("APP_LOG_LEVEL", "debug");
+
+        let actual = assert_config_loads::<MyLogConfig>(Environment::Develop);
+
+        std::env::remove_var("APP_LOG_LEVEL");
+
+        assert_eq!("debug", actual.log_level);
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(
+        expected = "Unable to load config for environment [develop]: Unable to deserialize into config with type avantis_utils::config::testing::tests::MyMissingFieldConfig"
+    )]
+    fn test_assert_config_loads_fail() {
+        assert_config_loads::<MyMissingFieldConfig>(Environment::Develop);
+    }
+
+    #[test]
+    #[serial]
+    fn test_verify_all_environments() {
+        let report = verify_all_environments::<MyLogConfig>("config");
+
+        assert!(!report.is_ok());
+        assert_eq!(vec![Environment::Local], report.failed_environments());
+        assert!(report.to_string().contains("[local]"));
+        assert!(report.to_string().contains("not found"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_verify_all_environments_aggregates_errors() {
+        let report = verify_all_environments::<MyMissingFieldConfig>("config");
+
+        assert_eq!(Environment::all().to_vec(), report.failed_environments());
+        assert!(report
+            .failures
+            .iter()
+            .skip(1)
+            .all(|(_, err)| err.contains("missing field `missing_field`")));
+        assert!(report
+            .to_string()
+            .starts_with("config at `config` fails to load for 4 environment(s):"));
+    }
+}
@@ -3,6 +3,8 @@
 //!
 //! By default, we use Postgres as our database at Avantis.
 
+use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
 
 use serde::Deserialize;
@@ -25,19 +27,140 @@ pub mod diesel;
 ///   user: "username".to_string(),
 ///   password: "REPLACE_ME".to_string(),
 ///   db_name: "my_db".to_string(),
-///   max_connections: 30
+///   max_connections: 30,
+///   min_connections: 0,
+///   validate_on_init: true,
+///   slow_query_threshold_ms: Some(500),
+///   statement_cache: true,
 /// };
 ///
+/// // password is masked, ie. `DatabaseConfig { host: "localhost", ..., password: ***, ... }`
 /// println!("{:?}", config);
 /// // initialize the pool by calling `config.init_pool().await?`
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct DatabaseConfig {
     pub host: String,
     pub user: String,
     pub password: String,
     pub db_name: String,
     pub max_connections: u32,
+    /// Connections kept open by sqlx pool. Warmed up front by
+    /// [init_pool_eager](crate::db::sqlx::SqlxDatabaseConfig::init_pool_eager).
+    #[serde(default)]
+    pub min_connections: u32,
+    /// Open a connection when the pool is initialized, failing early if the database is
+    /// unreachable. Disable for environments where the database may not be up yet at startup,
+    /// connections are then opened on first use. Default to `true`.
+    #[serde(default = "default_validate_on_init")]
+    pub validate_on_init: bool,
+    /// Log queries slower than this as `WARN` with elapsed time and SQL. sqlx only, logged
+    /// through `log` on target `sqlx::query`, forwarded to tracing once telemetry is initialized.
+    /// Default to sqlx threshold of 1 second.
+    #[serde(default)]
+    pub slow_query_threshold_ms: Option<u64>,
+    /// Cache prepared statements per connection. sqlx only. Disable behind PgBouncer in
+    /// transaction pooling mode, where a connection may be served by another server between
+    /// transactions. Default to `true`.
+    #[serde(default = "default_statement_cache")]
+    pub statement_cache: bool,
+}
+
+fn default_validate_on_init() -> bool {
+    true
+}
+
+fn default_statement_cache() -> bool {
+    true
+}
+
+/// Multiple named databases, ie. an OLTP and a reporting database. Deserialized from a map
+/// of name to [DatabaseConfig].
+///
+/// # Example
+///
+/// ```yaml
+/// databases:
+///   oltp:
+///     host: localhost
+///     ...
+///   reporting:
+///     host: reporting.localhost
+///     ...
+/// ```
+///
+/// Initialize every pool concurrently with `config.init_pools().await?`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct DatabasesConfig(pub HashMap<String, DatabaseConfig>);
+
+impl DatabasesConfig {
+    pub fn get(&self, name: &str) -> Option<&DatabaseConfig> {
+        self.0.get(name)
+    }
+}
+
+/// Primary database with its read replicas. Reads are spread over `readers`, or go to
+/// `writer` if there is none, so the same code runs with or without replicas.
+///
+/// # Example
+///
+/// ```yaml
+/// database:
+///   writer:
+///     host: localhost
+///     ...
+///   readers:
+///     - host: replica-1.localhost
+///       ...
+/// ```
+///
+/// Initialize pools with `config.init_pool().await?`, see `ReplicatedPool` of
+/// [sqlx](crate::db::sqlx) module.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
+pub struct ReplicatedDatabaseConfig {
+    pub writer: DatabaseConfig,
+    #[serde(default)]
+    pub readers: Vec<DatabaseConfig>,
+}
+
+impl fmt::Debug for DatabaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseConfig")
+            .field("host", &self.host)
+            .field("user", &self.user)
+            .field("password", &format_args!("***"))
+            .field("db_name", &self.db_name)
+            .field("max_connections", &self.max_connections)
+            .field("min_connections", &self.min_connections)
+            .field("validate_on_init", &self.validate_on_init)
+            .field("slow_query_threshold_ms", &self.slow_query_threshold_ms)
+            .field("statement_cache", &self.statement_cache)
+            .finish()
+    }
+}
+
+/// Coarse classification of database errors, ie. to decide whether an operation
+/// is worth retrying. See `classify_error` in [sqlx](crate::db::sqlx) and
+/// [diesel](crate::db::diesel) modules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbErrorKind {
+    /// Authentication or authorization failed. Retrying will not help.
+    Auth,
+    /// Database cannot be reached, ie. connection refused or reset.
+    Unreachable,
+    /// Connecting, acquiring a connection or running a statement timed out.
+    Timeout,
+    Other,
+}
+
+impl DbErrorKind {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DbErrorKind::Unreachable | DbErrorKind::Timeout)
+    }
 }
 
 impl DatabaseConfig {
@@ -71,11 +194,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_debug_masks_password() {
+        assert_eq!(
+            r#"DatabaseConfig { host: "localhost", user: "username", password: ***, db_name: "my_db", max_connections: 30, min_connections: 0, validate_on_init: true, slow_query_threshold_ms: None, statement_cache: true }"#,
+            format!("{:?}", *CONFIG),
+        );
+        assert!(!format!("{:#?}", *CONFIG).contains("supersecurepassword"));
+    }
+
     static CONFIG: Lazy<DatabaseConfig> = Lazy::new(|| DatabaseConfig {
         host: "localhost".to_string(),
         user: "username".to_string(),
         password: "supersecurepassword".to_string(),
         db_name: "my_db".to_string(),
         max_connections: 30,
+        min_connections: 0,
+        validate_on_init: true,
+        slow_query_threshold_ms: None,
+        statement_cache: true,
     });
 }
@@ -1,8 +1,13 @@
 use super::*;
 
+use ::diesel::connection::SimpleConnection;
 use ::diesel::pg::PgConnection;
-use ::diesel::r2d2::{ConnectionManager, Pool, PoolError, PooledConnection};
-use ::diesel::{Connection, ConnectionError};
+use ::diesel::r2d2::Error as ManagerError;
+use ::diesel::r2d2::{
+    ConnectionManager, CustomizeConnection, NopConnectionCustomizer, Pool, PoolError,
+    PooledConnection,
+};
+use ::diesel::ConnectionError;
 use thiserror::Error;
 use tracing::instrument;
 
@@ -10,22 +15,54 @@ pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 pub type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
 pub trait DieselDatabaseConfig {
+    /// Build a connection pool. With `validate_on_init`, wait for the pool to open its
+    /// connections and fail with [Error::PoolError] if none can be opened within
+    /// connection timeout. Otherwise, return the pool immediately.
     fn init_pool(&self) -> Result<PgPool, Error>;
+
+    /// Like [DieselDatabaseConfig::init_pool], but run `customizer` on every new
+    /// connection acquired by the pool. See [SessionSettings] for a ready-made one.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use avantis_utils::db::DatabaseConfig;
+    /// # use avantis_utils::db::diesel::{DieselDatabaseConfig, SessionSettings};
+    /// # fn example(config: DatabaseConfig) -> Result<(), avantis_utils::db::diesel::Error> {
+    /// let pool = config.init_pool_with_customizer(SessionSettings {
+    ///     time_zone: Some("UTC".to_string()),
+    ///     statement_timeout: Some(Duration::from_secs(30)),
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn init_pool_with_customizer<C>(&self, customizer: C) -> Result<PgPool, Error>
+    where
+        C: CustomizeConnection<PgConnection, ManagerError>;
 }
 
 impl DieselDatabaseConfig for DatabaseConfig {
-    #[instrument(skip_all, name = "db::diesel::init_pool", fields(host = %self.host, db = %self.db_name))]
     fn init_pool(&self) -> Result<PgPool, Error> {
-        let database_url = self.postgres_uri();
-        PgConnection::establish(&database_url)?;
+        self.init_pool_with_customizer(NopConnectionCustomizer)
+    }
 
-        let manager = ConnectionManager::<PgConnection>::new(database_url);
-        let pool = Pool::builder()
+    #[instrument(skip_all, name = "db::diesel::init_pool", fields(host = %self.host, db = %self.db_name))]
+    fn init_pool_with_customizer<C>(&self, customizer: C) -> Result<PgPool, Error>
+    where
+        C: CustomizeConnection<PgConnection, ManagerError>,
+    {
+        let manager = ConnectionManager::<PgConnection>::new(self.postgres_uri());
+        let builder = Pool::builder()
             .max_size(self.max_connections)
             .connection_timeout(self.connection_timeout())
-            .build(manager)?;
+            .connection_customizer(Box::new(customizer));
 
-        Ok(pool)
+        if self.validate_on_init {
+            Ok(builder.build(manager)?)
+        } else {
+            Ok(builder.build_unchecked(manager))
+        }
     }
 }
 
@@ -33,6 +70,87 @@ pub fn fetch_connection(pool: &PgPool) -> Result<PgPooledConnection, Error> {
     Ok(pool.get()?)
 }
 
+/// Connection customizer that applies Postgres session settings on every new
+/// connection. `None` settings are left as the server default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionSettings {
+    /// ie. `UTC` or `Asia/Bangkok`
+    pub time_zone: Option<String>,
+    /// truncated to milliseconds. zero disables the timeout.
+    pub statement_timeout: Option<Duration>,
+}
+
+impl SessionSettings {
+    fn sql(&self) -> String {
+        let mut sql = String::new();
+        if let Some(time_zone) = &self.time_zone {
+            sql.push_str(&format!(
+                "SET TIME ZONE '{}';",
+                time_zone.replace('\'', "''")
+            ));
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            sql.push_str(&format!(
+                "SET statement_timeout = {};",
+                statement_timeout.as_millis()
+            ));
+        }
+        sql
+    }
+}
+
+impl CustomizeConnection<PgConnection, ManagerError> for SessionSettings {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), ManagerError> {
+        let sql = self.sql();
+        if sql.is_empty() {
+            return Ok(());
+        }
+        conn.batch_execute(&sql).map_err(ManagerError::QueryError)
+    }
+}
+
+/// Classify a diesel [Error] into a [DbErrorKind].
+///
+/// diesel only exposes libpq and r2d2 errors as messages, so this matches on the
+/// message text. r2d2 timeouts carry the last connection error, which takes
+/// precedence, ie. a pool timeout caused by a wrong password is [DbErrorKind::Auth].
+pub fn classify_error(err: &Error) -> DbErrorKind {
+    match err {
+        Error::ConnectionError(ConnectionError::BadConnection(message)) => {
+            classify_message(message)
+        }
+        Error::ConnectionError(_) => DbErrorKind::Other,
+        Error::PoolError(err) => match classify_message(&err.to_string()) {
+            DbErrorKind::Other => DbErrorKind::Timeout,
+            kind => kind,
+        },
+    }
+}
+
+fn classify_message(message: &str) -> DbErrorKind {
+    let message = message.to_lowercase();
+    if message.contains("authentication failed")
+        || message.contains("password authentication")
+        || message.contains("no password supplied")
+        || message.contains("permission denied")
+        || message.contains("no pg_hba.conf entry")
+    {
+        DbErrorKind::Auth
+    } else if message.contains("timeout") || message.contains("timed out") {
+        DbErrorKind::Timeout
+    } else if message.contains("connection refused")
+        || message.contains("could not connect")
+        || message.contains("could not translate host name")
+        || message.contains("server closed the connection")
+        || message.contains("connection reset")
+        || message.contains("the database system is")
+    {
+        DbErrorKind::Unreachable
+    } else {
+        DbErrorKind::Other
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("connection error: `{0}`")]
@@ -40,3 +158,85 @@ pub enum Error {
     #[error("pool error: `{0}`")]
     PoolError(#[from] PoolError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_pool_without_validation() {
+        let config = DatabaseConfig {
+            host: "localhost:1".to_string(),
+            user: "username".to_string(),
+            password: "supersecurepassword".to_string(),
+            db_name: "my_db".to_string(),
+            max_connections: 1,
+            min_connections: 0,
+            validate_on_init: true,
+            slow_query_threshold_ms: None,
+            statement_cache: true,
+        };
+
+        assert!(matches!(config.init_pool(), Err(Error::PoolError(_))));
+
+        let config = DatabaseConfig {
+            validate_on_init: false,
+            ..config
+        };
+
+        assert!(config.init_pool().is_ok());
+    }
+
+    #[test]
+    fn test_classify_error() {
+        fn bad_connection(message: &str) -> Error {
+            ConnectionError::BadConnection(message.to_string()).into()
+        }
+
+        assert_eq!(
+            DbErrorKind::Auth,
+            classify_error(&bad_connection(
+                "FATAL:  password authentication failed for user \"username\""
+            ))
+        );
+        assert_eq!(
+            DbErrorKind::Unreachable,
+            classify_error(&bad_connection(
+                "could not connect to server: Connection refused"
+            ))
+        );
+        assert_eq!(
+            DbErrorKind::Timeout,
+            classify_error(&bad_connection("timeout expired"))
+        );
+        assert_eq!(
+            DbErrorKind::Other,
+            classify_error(&bad_connection("something else"))
+        );
+        assert_eq!(
+            DbErrorKind::Other,
+            classify_error(&ConnectionError::InvalidConnectionUrl("foo".to_string()).into())
+        );
+    }
+
+    #[test]
+    fn test_session_settings_sql() {
+        assert_eq!("", SessionSettings::default().sql());
+        assert_eq!(
+            "SET TIME ZONE 'UTC';SET statement_timeout = 30000;",
+            SessionSettings {
+                time_zone: Some("UTC".to_string()),
+                statement_timeout: Some(Duration::from_secs(30)),
+            }
+            .sql()
+        );
+        assert_eq!(
+            "SET TIME ZONE 'it''s';",
+            SessionSettings {
+                time_zone: Some("it's".to_string()),
+                statement_timeout: None,
+            }
+            .sql()
+        );
+    }
+}
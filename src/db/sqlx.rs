@@ -1,22 +1,227 @@
 use super::*;
 
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ::sqlx::postgres::PgConnectOptions;
 use ::sqlx::postgres::PgPoolOptions;
+#[cfg(feature = "telemetry")]
+use ::sqlx::postgres::PgQueryResult;
+use ::sqlx::ConnectOptions;
 use ::sqlx::Error;
 use ::sqlx::Pool;
 use ::sqlx::Postgres;
+use ::sqlx::Transaction;
+#[cfg(feature = "telemetry")]
+use ::sqlx::{Execute, Executor};
 use async_trait::async_trait;
+use log::LevelFilter;
+use thiserror::Error as ThisError;
 use tracing::instrument;
+use tracing::Instrument;
+
+use crate::util::retry::{retry_async, RetryPolicy};
 
 #[async_trait]
 pub trait SqlxDatabaseConfig {
     async fn init_pool(&self) -> Result<Pool<Postgres>, Error>;
+
+    /// Like [SqlxDatabaseConfig::init_pool], but customize connect options with `f` before
+    /// connecting, ie. to set `application_name` or `options`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::db::DatabaseConfig;
+    /// # use avantis_utils::db::sqlx::SqlxDatabaseConfig;
+    /// # async fn example(config: DatabaseConfig) -> Result<(), sqlx::Error> {
+    /// let pool = config
+    ///     .init_pool_with(|options| options.application_name("my-service"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn init_pool_with<F>(&self, f: F) -> Result<Pool<Postgres>, Error>
+    where
+        F: FnOnce(PgConnectOptions) -> PgConnectOptions + Send;
+
+    /// Like [SqlxDatabaseConfig::init_pool], but open `min_connections` connections before
+    /// returning, so the first requests don't pay connection cost. Fail if any of them
+    /// can't be opened, ie. to gate readiness of the service. `validate_on_init` is ignored.
+    async fn init_pool_eager(&self) -> Result<Pool<Postgres>, Error>;
+
+    /// Like [SqlxDatabaseConfig::init_pool], but set up the pool on the runtime of `handle`,
+    /// ie. when called outside of a tokio runtime or from a custom runtime. The pool stays
+    /// bound to that runtime, which must outlive it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::db::DatabaseConfig;
+    /// # use avantis_utils::db::sqlx::SqlxDatabaseConfig;
+    /// # async fn example(config: DatabaseConfig) -> Result<(), sqlx::Error> {
+    /// let runtime = tokio::runtime::Builder::new_multi_thread()
+    ///     .enable_all()
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let pool = config.init_pool_on(runtime.handle()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn init_pool_on(&self, handle: &tokio::runtime::Handle) -> Result<Pool<Postgres>, Error>;
 }
 
 #[async_trait]
 impl SqlxDatabaseConfig for DatabaseConfig {
-    #[instrument(skip_all, name = "db::sqlx::init_pool", fields(host = %self.host, db = %self.db_name))]
     async fn init_pool(&self) -> Result<Pool<Postgres>, Error> {
-        self.pool_options().connect(&self.postgres_uri()).await
+        self.init_pool_with(|options| options).await
+    }
+
+    #[instrument(skip_all, name = "db::sqlx::init_pool", fields(host = %self.host, db = %self.db_name))]
+    async fn init_pool_with<F>(&self, f: F) -> Result<Pool<Postgres>, Error>
+    where
+        F: FnOnce(PgConnectOptions) -> PgConnectOptions + Send,
+    {
+        let options = self.connect_options_with(f)?;
+        if self.validate_on_init {
+            self.pool_options().connect_with(options).await
+        } else {
+            Ok(self.pool_options().connect_lazy_with(options))
+        }
+    }
+
+    #[instrument(skip_all, name = "db::sqlx::init_pool_eager", fields(host = %self.host, db = %self.db_name, min_connections = self.min_connections))]
+    async fn init_pool_eager(&self) -> Result<Pool<Postgres>, Error> {
+        let pool = self.init_pool().await?;
+
+        let mut connections = Vec::with_capacity(self.warm_connections() as usize);
+        for _ in 0..self.warm_connections() {
+            connections.push(pool.acquire().await?);
+        }
+        drop(connections);
+
+        Ok(pool)
+    }
+
+    async fn init_pool_on(&self, handle: &tokio::runtime::Handle) -> Result<Pool<Postgres>, Error> {
+        let config = self.clone();
+
+        handle
+            .spawn(async move { config.init_pool().await })
+            .await
+            .unwrap_or(Err(Error::WorkerCrashed))
+    }
+}
+
+impl DatabasesConfig {
+    /// Initialize pools of every database concurrently, keyed by name.
+    /// If any of them fail, return the error of the first failed database by name.
+    #[instrument(skip_all, name = "db::sqlx::init_pools", fields(databases = self.0.len()))]
+    pub async fn init_pools(&self) -> Result<HashMap<String, Pool<Postgres>>, NamedPoolError> {
+        let mut names = self.0.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        let handles = names
+            .into_iter()
+            .map(|name| {
+                let config = self.0[&name].clone();
+                let span = tracing::info_span!("db::sqlx::init_named_pool", name = %name);
+                let handle = tokio::spawn(async move { config.init_pool().await }.instrument(span));
+                (name, handle)
+            })
+            .collect::<Vec<_>>();
+
+        let mut pools = HashMap::with_capacity(handles.len());
+        let mut first_error = None;
+        for (name, handle) in handles {
+            let result = handle
+                .await
+                .unwrap_or_else(|_| Err(Error::WorkerCrashed))
+                .map_err(|source| NamedPoolError {
+                    name: name.clone(),
+                    source,
+                });
+
+            match result {
+                Ok(pool) => {
+                    pools.insert(name, pool);
+                }
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(pools),
+        }
+    }
+}
+
+/// Error of [DatabasesConfig::init_pools], with name of the failed database.
+#[derive(ThisError, Debug)]
+#[error("unable to init pool of database `{name}`: {source}")]
+pub struct NamedPoolError {
+    pub name: String,
+    pub source: Error,
+}
+
+/// Pools of a primary database and its read replicas. Use [read] and [write] to pick the
+/// pool at the call site. Cheap to clone.
+#[derive(Clone, Debug)]
+pub struct ReplicatedPool {
+    writer: Pool<Postgres>,
+    readers: Vec<Pool<Postgres>>,
+    next_reader: Arc<AtomicUsize>,
+}
+
+impl ReplicatedPool {
+    pub fn new(writer: Pool<Postgres>, readers: Vec<Pool<Postgres>>) -> Self {
+        Self {
+            writer,
+            readers,
+            next_reader: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn writer(&self) -> &Pool<Postgres> {
+        &self.writer
+    }
+
+    /// Next reader in round robin, or the writer if there is no reader.
+    pub fn reader(&self) -> &Pool<Postgres> {
+        match self.reader_index() {
+            Some(index) => &self.readers[index],
+            None => &self.writer,
+        }
+    }
+
+    fn reader_index(&self) -> Option<usize> {
+        match self.readers.len() {
+            0 => None,
+            len => Some(self.next_reader.fetch_add(1, Ordering::Relaxed) % len),
+        }
+    }
+}
+
+impl ReplicatedDatabaseConfig {
+    /// Initialize pools of writer and readers with [SqlxDatabaseConfig::init_pool].
+    #[instrument(skip_all, name = "db::sqlx::init_replicated_pool", fields(host = %self.writer.host, readers = self.readers.len()))]
+    pub async fn init_pool(&self) -> Result<ReplicatedPool, Error> {
+        let writer = self.writer.init_pool().await?;
+
+        let mut readers = Vec::with_capacity(self.readers.len());
+        for reader in &self.readers {
+            readers.push(reader.init_pool().await?);
+        }
+
+        Ok(ReplicatedPool::new(writer, readers))
     }
 }
 
@@ -24,8 +229,287 @@ impl DatabaseConfig {
     fn pool_options(&self) -> PgPoolOptions {
         PgPoolOptions::new()
             .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
             .acquire_timeout(self.connection_timeout())
     }
+
+    /// Connections to open in [SqlxDatabaseConfig::init_pool_eager], at most `max_connections`.
+    fn warm_connections(&self) -> u32 {
+        self.min_connections.min(self.max_connections)
+    }
+
+    fn connect_options_with<F>(&self, f: F) -> Result<PgConnectOptions, Error>
+    where
+        F: FnOnce(PgConnectOptions) -> PgConnectOptions,
+    {
+        let mut options = PgConnectOptions::from_str(&self.postgres_uri())?;
+
+        if let Some(threshold_ms) = self.slow_query_threshold_ms {
+            options.log_slow_statements(LevelFilter::Warn, Duration::from_millis(threshold_ms));
+        }
+
+        if !self.statement_cache {
+            options = options.statement_cache_capacity(0);
+        }
+
+        Ok(f(options))
+    }
+}
+
+/// Classify a sqlx error into a [DbErrorKind].
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::db::DbErrorKind;
+/// # use avantis_utils::db::sqlx::classify_error;
+/// assert_eq!(DbErrorKind::Timeout, classify_error(&sqlx::Error::PoolTimedOut));
+/// assert!(classify_error(&sqlx::Error::PoolTimedOut).is_retryable());
+/// ```
+pub fn classify_error(err: &Error) -> DbErrorKind {
+    match err {
+        Error::Database(err) => match err.code().as_deref() {
+            // invalid_authorization_specification, invalid_password
+            Some("28000") | Some("28P01") => DbErrorKind::Auth,
+            // query_canceled, ie. statement_timeout
+            Some("57014") => DbErrorKind::Timeout,
+            // cannot_connect_now, too_many_connections, admin_shutdown
+            Some("57P03") | Some("53300") | Some("57P01") => DbErrorKind::Unreachable,
+            // connection_exception class
+            Some(code) if code.starts_with("08") => DbErrorKind::Unreachable,
+            _ => DbErrorKind::Other,
+        },
+        Error::Io(err) if err.kind() == std::io::ErrorKind::TimedOut => DbErrorKind::Timeout,
+        Error::Io(_) | Error::PoolClosed => DbErrorKind::Unreachable,
+        Error::PoolTimedOut => DbErrorKind::Timeout,
+        _ => DbErrorKind::Other,
+    }
+}
+
+/// SQL longer than this is truncated in [query_span].
+#[cfg(feature = "telemetry")]
+const MAX_TRACED_SQL_LENGTH: usize = 1024;
+
+/// Span for a single query, nested under current span. Record `db.statement` with SQL
+/// truncated to 1024 bytes. `db.rows_affected` and `elapsed_ms` are recorded by
+/// [query_traced].
+///
+/// Use it to instrument fetch queries, ie. `query.fetch_all(&pool).instrument(query_span(sql))`.
+///
+/// Require `telemetry` feature.
+#[cfg(feature = "telemetry")]
+pub fn query_span(sql: &str) -> tracing::Span {
+    tracing::info_span!(
+        "db::sqlx::query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.statement = truncate_sql(sql),
+        db.rows_affected = tracing::field::Empty,
+        elapsed_ms = tracing::field::Empty,
+    )
+}
+
+/// Execute `query` within a [query_span], recording rows affected and elapsed time.
+///
+/// Require `telemetry` feature.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::db::sqlx::query_traced;
+/// # async fn example(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+/// let result = query_traced(
+///     &pool,
+///     sqlx::query("UPDATE accounts SET balance = balance + $1 WHERE id = $2")
+///         .bind(100_i64)
+///         .bind(1_i64),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "telemetry")]
+pub async fn query_traced<'q, 'e, E, Q>(executor: E, query: Q) -> Result<PgQueryResult, Error>
+where
+    'q: 'e,
+    E: Executor<'e, Database = Postgres>,
+    Q: Execute<'q, Postgres> + 'q,
+{
+    let span = query_span(query.sql());
+    let started_at = std::time::Instant::now();
+
+    let result = executor.execute(query).instrument(span.clone()).await;
+
+    span.record("elapsed_ms", started_at.elapsed().as_millis() as u64);
+    match &result {
+        Ok(result) => {
+            span.record("db.rows_affected", result.rows_affected());
+        }
+        Err(err) => {
+            tracing::warn!(parent: &span, %err, "query failed");
+        }
+    }
+
+    result
+}
+
+#[cfg(feature = "telemetry")]
+fn truncate_sql(sql: &str) -> &str {
+    if sql.len() <= MAX_TRACED_SQL_LENGTH {
+        return sql;
+    }
+
+    let mut end = MAX_TRACED_SQL_LENGTH;
+    while !sql.is_char_boundary(end) {
+        end -= 1;
+    }
+    &sql[..end]
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Run `f` in a transaction and commit it. If the transaction fails with a
+/// serialization failure (`40001`) or a deadlock (`40P01`), it is rolled back and
+/// retried up to `max_retries` times with exponential backoff, see [retry_async].
+/// Any other error is returned immediately.
+///
+/// `f` may be called more than once, so it must not have side effects outside
+/// of the transaction.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::db::sqlx::with_retryable_transaction;
+/// # async fn example(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+/// let balance: i64 = with_retryable_transaction(
+///     &pool,
+///     |tx| {
+///         Box::pin(async move {
+///             sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+///                 .execute(&mut **tx)
+///                 .await?;
+///             sqlx::query_scalar("SELECT balance FROM accounts WHERE id = 1")
+///                 .fetch_one(&mut **tx)
+///                 .await
+///         })
+///     },
+///     3,
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn with_retryable_transaction<T, F>(
+    pool: &Pool<Postgres>,
+    f: F,
+    max_retries: u32,
+) -> Result<T, Error>
+where
+    for<'c> F: FnMut(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+    T: Send,
+{
+    let policy = RetryPolicy {
+        max_attempts: max_retries.saturating_add(1),
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_secs(1),
+        ..RetryPolicy::default()
+    };
+    // only locked to start `f`, each attempt runs after the previous one.
+    let f = Mutex::new(f);
+
+    retry_async(&policy, is_retryable_transaction_error, || async {
+        let mut tx = pool.begin().await?;
+        let transaction = (f.lock().unwrap())(&mut tx);
+        let value = transaction.await?;
+        tx.commit().await?;
+        Ok(value)
+    })
+    .await
+}
+
+/// Run `f` in a read only transaction on a reader of `pool`. A write in `f` fails with
+/// `read_only_sql_transaction` (`25006`), even when it falls back to the writer, and is
+/// logged as a warning with the type of `f` to find the caller.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::db::sqlx::{read, ReplicatedPool};
+/// # async fn example(pool: ReplicatedPool) -> Result<(), sqlx::Error> {
+/// let balance: i64 = read(&pool, |tx| {
+///     Box::pin(async move {
+///         sqlx::query_scalar("SELECT balance FROM accounts WHERE id = 1")
+///             .fetch_one(&mut **tx)
+///             .await
+///     })
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn read<T, F>(pool: &ReplicatedPool, f: F) -> Result<T, Error>
+where
+    for<'c> F: FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    let mut tx = pool.reader().begin().await?;
+    ::sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let result = f(&mut tx).await;
+    if let Err(err) = &result {
+        if is_read_only_violation(err) {
+            tracing::warn!(%err, caller = std::any::type_name::<F>(), "write attempted in db::sqlx::read");
+        }
+    }
+
+    let value = result?;
+    tx.commit().await?;
+    Ok(value)
+}
+
+/// Run `f` in a transaction on the writer of `pool` and commit it.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::db::sqlx::{write, ReplicatedPool};
+/// # async fn example(pool: ReplicatedPool) -> Result<(), sqlx::Error> {
+/// write(&pool, |tx| {
+///     Box::pin(async move {
+///         sqlx::query("UPDATE accounts SET balance = 0 WHERE id = 1")
+///             .execute(&mut **tx)
+///             .await
+///     })
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn write<T, F>(pool: &ReplicatedPool, f: F) -> Result<T, Error>
+where
+    for<'c> F: FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    let mut tx = pool.writer().begin().await?;
+    let value = f(&mut tx).await?;
+    tx.commit().await?;
+    Ok(value)
+}
+
+fn is_read_only_violation(err: &Error) -> bool {
+    match err {
+        // read_only_sql_transaction
+        Error::Database(err) => err.code().as_deref() == Some("25006"),
+        _ => false,
+    }
+}
+
+fn is_retryable_transaction_error(err: &Error) -> bool {
+    match err {
+        // serialization_failure, deadlock_detected
+        Error::Database(err) => matches!(err.code().as_deref(), Some("40001") | Some("40P01")),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -51,11 +535,219 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_init_pools() {
+        assert!(DatabasesConfig::default()
+            .init_pools()
+            .await
+            .unwrap()
+            .is_empty());
+
+        let config = DatabasesConfig(HashMap::from([
+            ("oltp".to_string(), CONFIG.clone()),
+            ("reporting".to_string(), CONFIG.clone()),
+        ]));
+
+        let err = config.init_pools().await.unwrap_err();
+
+        assert_eq!("oltp", err.name);
+        assert!(err
+            .to_string()
+            .starts_with("unable to init pool of database `oltp`: "));
+    }
+
+    #[tokio::test]
+    async fn test_init_pool_without_validation() {
+        assert!(CONFIG.init_pool().await.is_err());
+
+        let config = DatabaseConfig {
+            validate_on_init: false,
+            ..CONFIG.clone()
+        };
+
+        assert!(config.init_pool().await.is_ok());
+        assert!(config
+            .init_pool_with(|options| options.application_name("my-service"))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_init_pool_on() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // Stand-in for a custom executor, without IO and time drivers.
+        let executor = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let config = DatabaseConfig {
+            validate_on_init: false,
+            ..CONFIG.clone()
+        };
+
+        assert!(executor
+            .block_on(config.init_pool_on(runtime.handle()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_warm_connections() {
+        let config = DatabaseConfig {
+            min_connections: 5,
+            ..CONFIG.clone()
+        };
+
+        assert_eq!(5, config.warm_connections());
+
+        let config = DatabaseConfig {
+            max_connections: 2,
+            ..config
+        };
+
+        assert_eq!(2, config.warm_connections());
+    }
+
+    #[test]
+    fn test_classify_error() {
+        use std::io;
+
+        assert_eq!(
+            DbErrorKind::Unreachable,
+            classify_error(&Error::Io(io::ErrorKind::ConnectionRefused.into()))
+        );
+        assert_eq!(
+            DbErrorKind::Timeout,
+            classify_error(&Error::Io(io::ErrorKind::TimedOut.into()))
+        );
+        assert_eq!(DbErrorKind::Unreachable, classify_error(&Error::PoolClosed));
+        assert_eq!(DbErrorKind::Timeout, classify_error(&Error::PoolTimedOut));
+        assert_eq!(DbErrorKind::Other, classify_error(&Error::RowNotFound));
+    }
+
+    #[test]
+    #[cfg(feature = "telemetry")]
+    fn test_truncate_sql() {
+        assert_eq!("SELECT 1", truncate_sql("SELECT 1"));
+
+        let long_sql = format!("SELECT '{}'", "a".repeat(2000));
+        assert_eq!(&long_sql[..1024], truncate_sql(&long_sql));
+
+        let multi_byte_sql = format!("SELECT '{}'", "ก".repeat(1000));
+        let truncated = truncate_sql(&multi_byte_sql);
+        assert!(truncated.len() <= 1024);
+        assert!(multi_byte_sql.starts_with(truncated));
+    }
+
+    #[tokio::test]
+    async fn test_replicated_pool() {
+        let pool = || {
+            CONFIG
+                .pool_options()
+                .connect_lazy_with(CONFIG.connect_options_with(|options| options).unwrap())
+        };
+
+        let pool_without_readers = ReplicatedPool::new(pool(), vec![]);
+
+        assert_eq!(None, pool_without_readers.reader_index());
+
+        let pool = ReplicatedPool::new(pool(), vec![pool(), pool()]);
+        let cloned = pool.clone();
+
+        assert_eq!(Some(0), pool.reader_index());
+        assert_eq!(Some(1), cloned.reader_index());
+        assert_eq!(Some(0), pool.reader_index());
+
+        let result = read(&pool, |tx| {
+            Box::pin(async move { ::sqlx::query("SELECT 1").execute(&mut **tx).await })
+        })
+        .await;
+        assert!(result.is_err());
+
+        let config = ReplicatedDatabaseConfig {
+            writer: CONFIG.clone(),
+            readers: vec![CONFIG.clone()],
+        };
+        assert!(config.init_pool().await.is_err());
+    }
+
+    #[test]
+    fn test_is_read_only_violation() {
+        assert!(!is_read_only_violation(&Error::PoolTimedOut));
+        assert!(!is_read_only_violation(&Error::RowNotFound));
+    }
+
+    #[test]
+    fn test_is_retryable_transaction_error() {
+        assert!(!is_retryable_transaction_error(&Error::PoolTimedOut));
+        assert!(!is_retryable_transaction_error(&Error::RowNotFound));
+    }
+
+    #[test]
+    fn test_slow_query_threshold() {
+        let config = DatabaseConfig {
+            slow_query_threshold_ms: Some(200),
+            ..CONFIG.clone()
+        };
+
+        let debug = format!(
+            "{:?}",
+            config.connect_options_with(|options| options).unwrap()
+        );
+
+        assert!(
+            debug.contains("slow_statements_level: Warn, slow_statements_duration: 200ms"),
+            "unexpected options: {}",
+            debug
+        );
+    }
+
+    #[test]
+    fn test_statement_cache() {
+        let options = |config: &DatabaseConfig| {
+            format!(
+                "{:?}",
+                config.connect_options_with(|options| options).unwrap()
+            )
+        };
+
+        assert!(options(&CONFIG).contains("statement_cache_capacity: 100"));
+
+        let config = DatabaseConfig {
+            statement_cache: false,
+            ..CONFIG.clone()
+        };
+
+        assert!(options(&config).contains("statement_cache_capacity: 0"));
+    }
+
+    #[test]
+    fn test_connect_options_with() {
+        let options = CONFIG
+            .connect_options_with(|options| {
+                options
+                    .application_name("my-service")
+                    .options([("search_path", "my_schema")])
+            })
+            .unwrap();
+
+        let debug = format!("{:?}", options);
+
+        assert_eq!(Some("my_db"), options.get_database());
+        assert!(debug.contains(r#"application_name: Some("my-service")"#));
+        assert!(debug.contains(r#"options: Some("-c search_path=my_schema")"#));
+    }
+
     static CONFIG: Lazy<DatabaseConfig> = Lazy::new(|| DatabaseConfig {
         host: "localhost".to_string(),
         user: "username".to_string(),
         password: "supersecurepassword".to_string(),
         db_name: "my_db".to_string(),
         max_connections: 30,
+        min_connections: 0,
+        validate_on_init: true,
+        slow_query_threshold_ms: None,
+        statement_cache: true,
     });
 }
@@ -1,17 +1,156 @@
+use anyhow::anyhow;
+use anyhow::Result;
 use bytes::Bytes;
+use prost::DecodeError;
 use rdkafka::producer::FutureRecord;
 use serde::Deserialize;
 use std::ops::Deref;
 
+pub mod admin;
 pub mod consumer;
+#[cfg(feature = "kafka-metrics")]
+mod metrics;
 pub mod producer;
+#[cfg(feature = "test-util")]
+pub mod testing;
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct KafkaConfig {
     pub brokers_csv: String,
     pub flush_duration_millis: u64,
     pub poll_duration_millis: u64,
     pub security_protocol: Option<String>,
+    /// `message.timeout.ms` of producers, default to `30000`.
+    pub message_timeout_millis: Option<u64>,
+    /// `acks` of producers, ie. `all`, `1` or `0`. Default to librdkafka default, `all`.
+    pub acks: Option<String>,
+    /// Log level of librdkafka in producers, ie. `error`, `warning`, `info` or `debug`.
+    /// Default to `warning`.
+    pub log_level: Option<String>,
+}
+
+impl KafkaConfig {
+    /// Brokers listed in `brokers_csv`, trimmed. Blank entries are ignored.
+    pub fn brokers(&self) -> Vec<&str> {
+        self.brokers_csv
+            .split(',')
+            .map(|broker| broker.trim())
+            .filter(|broker| !broker.is_empty())
+            .collect()
+    }
+
+    /// `security_protocol`, default to `ssl`.
+    fn security_protocol(&self) -> &str {
+        self.security_protocol.as_deref().unwrap_or("ssl")
+    }
+
+    /// Create a [KafkaConfigBuilder] for programmatic construction.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// let config = KafkaConfig::builder()
+    ///     .brokers_csv("localhost:9092")
+    ///     .security_protocol("plaintext")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> KafkaConfigBuilder {
+        KafkaConfigBuilder::default()
+    }
+}
+
+/// Builder for [KafkaConfig]. See [KafkaConfig::builder].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KafkaConfigBuilder {
+    brokers_csv: String,
+    flush_duration_millis: u64,
+    poll_duration_millis: u64,
+    security_protocol: Option<String>,
+    message_timeout_millis: Option<u64>,
+    acks: Option<String>,
+    log_level: Option<String>,
+}
+
+impl Default for KafkaConfigBuilder {
+    fn default() -> Self {
+        Self {
+            brokers_csv: String::new(),
+            flush_duration_millis: 10000,
+            poll_duration_millis: 1,
+            security_protocol: None,
+            message_timeout_millis: None,
+            acks: None,
+            log_level: None,
+        }
+    }
+}
+
+impl KafkaConfigBuilder {
+    pub fn brokers_csv(mut self, brokers_csv: impl Into<String>) -> Self {
+        self.brokers_csv = brokers_csv.into();
+        self
+    }
+
+    pub fn brokers<S: AsRef<str>>(mut self, brokers: &[S]) -> Self {
+        self.brokers_csv = brokers
+            .iter()
+            .map(|broker| broker.as_ref())
+            .collect::<Vec<_>>()
+            .join(",");
+        self
+    }
+
+    pub fn flush_duration_millis(mut self, flush_duration_millis: u64) -> Self {
+        self.flush_duration_millis = flush_duration_millis;
+        self
+    }
+
+    pub fn poll_duration_millis(mut self, poll_duration_millis: u64) -> Self {
+        self.poll_duration_millis = poll_duration_millis;
+        self
+    }
+
+    pub fn security_protocol(mut self, security_protocol: impl Into<String>) -> Self {
+        self.security_protocol = Some(security_protocol.into());
+        self
+    }
+
+    pub fn message_timeout_millis(mut self, message_timeout_millis: u64) -> Self {
+        self.message_timeout_millis = Some(message_timeout_millis);
+        self
+    }
+
+    pub fn acks(mut self, acks: impl Into<String>) -> Self {
+        self.acks = Some(acks.into());
+        self
+    }
+
+    pub fn log_level(mut self, log_level: impl Into<String>) -> Self {
+        self.log_level = Some(log_level.into());
+        self
+    }
+
+    /// Build [KafkaConfig]. Fail if no broker is given.
+    pub fn build(self) -> Result<KafkaConfig> {
+        let config = KafkaConfig {
+            brokers_csv: self.brokers_csv,
+            flush_duration_millis: self.flush_duration_millis,
+            poll_duration_millis: self.poll_duration_millis,
+            security_protocol: self.security_protocol,
+            message_timeout_millis: self.message_timeout_millis,
+            acks: self.acks,
+            log_level: self.log_level,
+        };
+
+        if config.brokers().is_empty() {
+            return Err(anyhow!("kafka brokers must not be empty"));
+        }
+
+        Ok(config)
+    }
 }
 
 pub struct ProtobufKafkaRecord<'a> {
@@ -24,6 +163,140 @@ pub struct ProtobufKafkaMessage {
     pub value: Bytes,
 }
 
+impl ProtobufKafkaMessage {
+    /// Create a message with protobuf encoded `message` as value.
+    pub fn encode<T: prost::Message>(key: impl Into<String>, message: &T) -> Self {
+        Self {
+            key: key.into(),
+            value: Bytes::from(message.encode_to_vec()),
+        }
+    }
+
+    /// Decode value back into protobuf `T`, the same way consumers do.
+    /// Useful to check produced messages in tests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::ProtobufKafkaMessage;
+    /// #[derive(Clone, PartialEq, prost::Message)]
+    /// struct Greeting {
+    ///     #[prost(string, tag = "1")]
+    ///     message: String,
+    /// }
+    ///
+    /// let greeting = Greeting { message: "hello".to_string() };
+    /// let message = ProtobufKafkaMessage::encode("key", &greeting);
+    ///
+    /// assert_eq!(greeting, message.decode::<Greeting>().unwrap());
+    /// ```
+    pub fn decode<T: prost::Message + Default>(&self) -> Result<T> {
+        T::decode(self.value.deref()).map_err(|err| {
+            anyhow!(
+                "Unable to decode message with key {} into type {} with error: {}",
+                self.key,
+                std::any::type_name::<T>(),
+                err
+            )
+        })
+    }
+}
+
+/// Protobuf message with the schema version of its type, for topics whose schema changes
+/// incompatibly. Encoded value is the version byte followed by the protobuf encoded message.
+/// Consume with
+/// [ConsumerExt::process_versioned_protobuf_and_commit](consumer::ConsumerExt::process_versioned_protobuf_and_commit).
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::kafka::{ProtobufKafkaRecord, VersionedProtobufMessage};
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct GreetingV2 {
+///     #[prost(string, tag = "1")]
+///     message: String,
+/// }
+///
+/// let greeting = GreetingV2 { message: "hello".to_string() };
+/// let record = ProtobufKafkaRecord {
+///     topic: "greetings",
+///     message: VersionedProtobufMessage::new(2, greeting).encode("key"),
+/// };
+///
+/// assert_eq!(2, record.message.value[0]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedProtobufMessage<T> {
+    pub version: u8,
+    pub message: T,
+}
+
+impl<T: prost::Message> VersionedProtobufMessage<T> {
+    pub fn new(version: u8, message: T) -> Self {
+        Self { version, message }
+    }
+
+    /// Encode into a message with `key`, ready to be sent like other protobuf messages.
+    pub fn encode(&self, key: impl Into<String>) -> ProtobufKafkaMessage {
+        let mut value = Vec::with_capacity(1 + self.message.encoded_len());
+        value.push(self.version);
+        self.message
+            .encode(&mut value)
+            .expect("vec has enough capacity");
+
+        ProtobufKafkaMessage {
+            key: key.into(),
+            value: Bytes::from(value),
+        }
+    }
+}
+
+/// Type decoded from versioned messages, usually an enum with a variant per schema version.
+/// See [VersionedProtobufMessage].
+///
+/// # Example
+///
+/// ```
+/// # use prost::{DecodeError, Message};
+/// # use avantis_utils::kafka::VersionedProtobuf;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct GreetingV1 {
+/// #     #[prost(string, tag = "1")]
+/// #     message: String,
+/// # }
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct GreetingV2 {
+/// #     #[prost(string, tag = "1")]
+/// #     message: String,
+/// # }
+/// enum Greeting {
+///     V1(GreetingV1),
+///     V2(GreetingV2),
+/// }
+///
+/// impl VersionedProtobuf for Greeting {
+///     fn decode_version(version: u8, payload: &[u8]) -> Option<Result<Self, DecodeError>> {
+///         match version {
+///             1 => Some(GreetingV1::decode(payload).map(Greeting::V1)),
+///             2 => Some(GreetingV2::decode(payload).map(Greeting::V2)),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait VersionedProtobuf: Sized + Send {
+    /// Decode protobuf `payload` of schema `version`. Return `None` for unsupported versions.
+    fn decode_version(version: u8, payload: &[u8]) -> Option<Result<Self, DecodeError>>;
+}
+
+/// Version and protobuf payload of a value encoded by [VersionedProtobufMessage::encode].
+/// Return `None` for empty value.
+pub(crate) fn split_version(value: &[u8]) -> Option<(u8, &[u8])> {
+    value
+        .split_first()
+        .map(|(version, payload)| (*version, payload))
+}
+
 impl<'a> From<&'a ProtobufKafkaRecord<'a>> for FutureRecord<'a, String, [u8]> {
     fn from(record: &'a ProtobufKafkaRecord<'a>) -> FutureRecord<'a, String, [u8]> {
         FutureRecord::to(record.topic)
@@ -31,3 +304,110 @@ impl<'a> From<&'a ProtobufKafkaRecord<'a>> for FutureRecord<'a, String, [u8]> {
             .payload(record.message.value.deref())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let expected = KafkaConfig {
+            brokers_csv: "localhost:9092,localhost:9093".to_string(),
+            flush_duration_millis: 5000,
+            poll_duration_millis: 1,
+            security_protocol: Some("plaintext".to_string()),
+            message_timeout_millis: Some(5000),
+            acks: Some("1".to_string()),
+            log_level: None,
+        };
+
+        let actual = KafkaConfig::builder()
+            .brokers(&["localhost:9092", "localhost:9093"])
+            .flush_duration_millis(5000)
+            .security_protocol("plaintext")
+            .message_timeout_millis(5000)
+            .acks("1")
+            .build()
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_brokers() {
+        let config = KafkaConfig::builder()
+            .brokers_csv(" localhost:9092, localhost:9093 ,")
+            .build()
+            .unwrap();
+
+        assert_eq!(vec!["localhost:9092", "localhost:9093"], config.brokers());
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TestMessage {
+        #[prost(string, tag = "1")]
+        message: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct OtherMessage {
+        #[prost(int64, tag = "1")]
+        count: i64,
+    }
+
+    #[test]
+    fn test_protobuf_message_round_trip() {
+        let expected = TestMessage {
+            message: "hello".to_string(),
+        };
+
+        let message = ProtobufKafkaMessage::encode("key", &expected);
+
+        assert_eq!("key", message.key);
+        assert_eq!(expected, message.decode::<TestMessage>().unwrap());
+    }
+
+    #[test]
+    fn test_protobuf_message_decode_mismatch() {
+        let message = ProtobufKafkaMessage::encode(
+            "key",
+            &TestMessage {
+                message: "hello".to_string(),
+            },
+        );
+
+        let err = message.decode::<OtherMessage>().unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("Unable to decode message with key key into type"));
+    }
+
+    #[test]
+    fn test_versioned_protobuf_message() {
+        use prost::Message;
+
+        let message = VersionedProtobufMessage::new(
+            3,
+            TestMessage {
+                message: "hello".to_string(),
+            },
+        )
+        .encode("key");
+
+        let (version, payload) = split_version(&message.value).unwrap();
+
+        assert_eq!("key", message.key);
+        assert_eq!(3, version);
+        assert_eq!("hello", TestMessage::decode(payload).unwrap().message);
+
+        assert_eq!(Some((1, [].as_ref())), split_version(&[1]));
+        assert_eq!(None, split_version(&[]));
+    }
+
+    #[test]
+    #[should_panic(expected = "kafka brokers must not be empty")]
+    fn test_builder_without_brokers() {
+        KafkaConfig::builder().build().unwrap();
+    }
+}
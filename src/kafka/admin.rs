@@ -0,0 +1,63 @@
+use rdkafka::error::KafkaResult;
+use rdkafka::ClientConfig;
+use tracing::instrument;
+
+use super::KafkaConfig;
+
+pub use rdkafka::admin::{AdminClient, AdminOptions};
+pub use rdkafka::client::DefaultClientContext;
+
+impl KafkaConfig {
+    /// Create an admin client with brokers and security settings of this config, ie. to
+    /// describe configs, list consumer groups or delete records. The client is thread safe,
+    /// create it once and share it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// # use avantis_utils::kafka::admin::AdminOptions;
+    /// # use rdkafka::admin::ResourceSpecifier;
+    /// # async fn example(config: KafkaConfig) -> rdkafka::error::KafkaResult<()> {
+    /// let admin = config.admin_client()?;
+    ///
+    /// let configs = admin
+    ///     .describe_configs(&[ResourceSpecifier::Topic("orders")], &AdminOptions::new())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip_all, name = "kafka::init_admin_client", fields(brokers = %self.brokers_csv))]
+    pub fn admin_client(&self) -> KafkaResult<AdminClient<DefaultClientContext>> {
+        ClientConfig::new()
+            .set("bootstrap.servers", self.brokers().join(","))
+            .set("security.protocol", self.security_protocol())
+            .create()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_client() {
+        let config = KafkaConfig::builder()
+            .brokers_csv("localhost:9092")
+            .security_protocol("plaintext")
+            .build()
+            .unwrap();
+
+        assert!(config.admin_client().is_ok());
+
+        let config = KafkaConfig {
+            security_protocol: Some("invalid".to_string()),
+            ..config
+        };
+
+        assert!(matches!(
+            config.admin_client(),
+            Err(rdkafka::error::KafkaError::ClientConfig(..))
+        ));
+    }
+}
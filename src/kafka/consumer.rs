@@ -2,24 +2,32 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::future::Future;
+use std::pin::Pin;
 use std::str::Utf8Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use futures_lite::{Stream, StreamExt};
 use opentelemetry::global;
+use opentelemetry::Context;
 use prost::DecodeError;
-use rdkafka::config::FromClientConfig;
+use rdkafka::config::{FromClientConfig, FromClientConfigAndContext};
 use rdkafka::consumer::{ConsumerContext, Rebalance};
-use rdkafka::error::{KafkaError, KafkaResult};
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
 use rdkafka::message::BorrowedMessage;
 use rdkafka::message::Headers;
-use rdkafka::{ClientConfig, ClientContext, Message, TopicPartitionList};
+use rdkafka::util::Timeout;
+use rdkafka::{ClientConfig, ClientContext, Message, Offset, TopicPartitionList};
 use thiserror::Error;
 use tracing::instrument;
-use tracing::{debug, error, info, warn};
+use tracing::Instrument;
+use tracing::{debug, info, warn};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use super::KafkaConfig;
+use super::{split_version, KafkaConfig, VersionedProtobuf};
+use crate::util::retry::RetryPolicy;
 
 pub use rdkafka::consumer::{
     CommitMode, Consumer, DefaultConsumerContext, MessageStream, StreamConsumer,
@@ -31,57 +39,334 @@ impl KafkaConfig {
     where
         T: FromClientConfig,
     {
-        ClientConfig::new()
+        self.consumer_client_config(group_id).create()
+    }
+
+    /// Like [KafkaConfig::consumer_config], but create the consumer with `context`, ie.
+    /// [LoggingConsumerContext] to log rebalances and commits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// # use avantis_utils::kafka::consumer::{LoggingConsumerContext, StreamConsumer};
+    /// # fn example(config: KafkaConfig) -> rdkafka::error::KafkaResult<()> {
+    /// let consumer: StreamConsumer<LoggingConsumerContext> =
+    ///     config.consumer_config_with_context("my-group", LoggingConsumerContext)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip_all, name = "kafka::init_consumer", fields(brokers = %self.brokers_csv, group = group_id))]
+    pub fn consumer_config_with_context<T, C>(&self, group_id: &str, context: C) -> KafkaResult<T>
+    where
+        T: FromClientConfigAndContext<C>,
+        C: ConsumerContext,
+    {
+        self.consumer_client_config(group_id)
+            .create_with_context(context)
+    }
+
+    /// Like [KafkaConfig::consumer_config], but with `enable.auto.offset.store=false`, so only
+    /// offsets stored by [ConsumerExt::store_offset_for] are committed by
+    /// [ConsumerExt::commit_stored]. Use it for [ConsumerExt::consume_protobuf].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// # use avantis_utils::kafka::consumer::StreamConsumer;
+    /// # fn example(config: KafkaConfig) -> rdkafka::error::KafkaResult<()> {
+    /// let consumer: StreamConsumer = config.consumer_config_manual_store("my-group")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip_all, name = "kafka::init_consumer", fields(brokers = %self.brokers_csv, group = group_id))]
+    pub fn consumer_config_manual_store<T>(&self, group_id: &str) -> KafkaResult<T>
+    where
+        T: FromClientConfig,
+    {
+        self.manual_store_client_config(group_id).create()
+    }
+
+    /// Like [KafkaConfig::consumer_config], but consume only the given partitions with
+    /// `assign` instead of `subscribe`, ie. for consumers owning state of specific partitions.
+    /// Partitions are not rebalanced among consumers of `group_id`, which is still used to
+    /// commit offsets.
+    ///
+    /// Each assignment is `(topic, partition, offset)` to start from. [Offset::Stored] starts
+    /// from the committed offset of `group_id`, falling back to `auto.offset.reset`.
+    /// [Offset::Beginning], [Offset::End], [Offset::OffsetTail] and non-negative
+    /// [Offset::Offset] are accepted as well. Fail on other offsets, negative partitions or
+    /// duplicated partitions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// # use avantis_utils::kafka::consumer::StreamConsumer;
+    /// # use rdkafka::Offset;
+    /// # async fn example(config: KafkaConfig) -> rdkafka::error::KafkaResult<()> {
+    /// let consumer: StreamConsumer = config.assigned_consumer(
+    ///     "my-group",
+    ///     &[("orders", 0, Offset::Stored), ("orders", 1, Offset::Offset(42))],
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip_all, name = "kafka::init_assigned_consumer", fields(brokers = %self.brokers_csv, group = group_id, ?assignments))]
+    pub fn assigned_consumer<T>(
+        &self,
+        group_id: &str,
+        assignments: &[(&str, i32, Offset)],
+    ) -> KafkaResult<T>
+    where
+        T: FromClientConfig + Consumer,
+    {
+        let assignments = assignment_list(assignments)?;
+        let consumer: T = self.consumer_config(group_id)?;
+        consumer.assign(&assignments)?;
+
+        Ok(consumer)
+    }
+
+    fn consumer_client_config(&self, group_id: &str) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config
             .set("group.id", group_id)
-            .set("bootstrap.servers", &self.brokers_csv)
+            .set("bootstrap.servers", self.brokers().join(","))
             .set("enable.partition.eof", "false")
-            .set(
-                "security.protocol",
-                self.security_protocol
-                    .clone()
-                    .unwrap_or_else(|| "ssl".to_string()),
-            )
+            .set("security.protocol", self.security_protocol())
             .set("session.timeout.ms", "6000")
             .set("enable.auto.commit", "false")
-            .set("auto.offset.reset", "earliest")
-            .create()
-    }
-}
-
-pub fn set_trace(message: &BorrowedMessage) -> Result<(), KakfaProcessError> {
-    if let Some(header) = message.headers() {
-        let trace_parent = std::str::from_utf8(
-            header
-                .get(0)
-                .ok_or_else(|| {
-                    KakfaProcessError::ParseHeaderError("header 0 not found".to_string())
-                })?
-                .1,
-        )?;
-        let trace_state = std::str::from_utf8(
-            header
-                .get(1)
-                .ok_or_else(|| {
-                    KakfaProcessError::ParseHeaderError("header 1 not found".to_string())
-                })?
-                .1,
-        )?;
-
-        let mut trace_metadata = HashMap::<String, String>::new();
-        trace_metadata.insert("traceparent".to_string(), trace_parent.to_owned());
-        trace_metadata.insert("tracestate".to_string(), trace_state.to_owned());
-
-        let parent_cx = global::get_text_map_propagator(|prop| prop.extract(&trace_metadata));
+            .set("auto.offset.reset", "earliest");
+        config
+    }
+
+    fn manual_store_client_config(&self, group_id: &str) -> ClientConfig {
+        let mut config = self.consumer_client_config(group_id);
+        config.set("enable.auto.offset.store", "false");
+        config
+    }
+}
+
+/// Set parent of current span from `traceparent` and `tracestate` headers of the message.
+/// Missing headers are not an error, trace propagation is skipped instead.
+/// Only fail if headers exist but are not valid utf-8.
+pub fn set_trace<M: Message>(message: &M) -> Result<(), KakfaProcessError> {
+    if let Some(parent_cx) = trace_context(message)? {
         tracing::Span::current().set_parent(parent_cx);
     }
+
     Ok(())
 }
 
+/// Trace context from `traceparent` and `tracestate` headers of the message, if any.
+fn trace_context<M: Message>(message: &M) -> Result<Option<Context>, KakfaProcessError> {
+    let headers = match message.headers() {
+        Some(headers) => headers,
+        None => {
+            debug!("message has no headers, skip trace propagation");
+            return Ok(None);
+        }
+    };
+
+    let mut trace_metadata = HashMap::<String, String>::new();
+    for index in 0..headers.count() {
+        match headers.get(index) {
+            Some((key, value)) if key == "traceparent" || key == "tracestate" => {
+                trace_metadata.insert(key.to_string(), std::str::from_utf8(value)?.to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    if !trace_metadata.contains_key("traceparent") {
+        debug!("message has no traceparent header, skip trace propagation");
+        return Ok(None);
+    }
+
+    Ok(Some(global::get_text_map_propagator(|prop| {
+        prop.extract(&trace_metadata)
+    })))
+}
+
+/// Message decoded into protobuf `T`, yielded by [ConsumerExt::protobuf_stream].
+pub struct DecodedMessage<M, T> {
+    /// Raw message, ie. to commit with [Consumer::commit_message] or
+    /// [ConsumerExt::store_offset_for] after `value` is processed.
+    pub message: M,
+    pub value: T,
+    /// Span with parent set from trace headers of the message. Instrument processing of
+    /// `value` with it to continue the producer trace.
+    pub span: tracing::Span,
+}
+
+pub type ProtobufStream<'a, T> = Pin<
+    Box<
+        dyn Stream<Item = Result<DecodedMessage<BorrowedMessage<'a>, T>, KakfaProcessError>>
+            + Send
+            + 'a,
+    >,
+>;
+
+fn decode_message<M, T>(message: M) -> Result<DecodedMessage<M, T>, KakfaProcessError>
+where
+    M: Message,
+    T: prost::Message + Default,
+{
+    let span = tracing::info_span!(
+        "kafka::consume",
+        topic = message.topic(),
+        partition = message.partition(),
+        offset = message.offset()
+    );
+    match trace_context(&message) {
+        Ok(Some(parent_cx)) => span.set_parent(parent_cx),
+        Ok(None) => {}
+        Err(err) => warn!("set trace fail with error `{}`", err),
+    }
+
+    let value = decode_protobuf::<T, M>(&message)?;
+
+    Ok(DecodedMessage {
+        message,
+        value,
+        span,
+    })
+}
+
+/// Metadata of a consumed message, passed to [ConsumerExt::process_protobuf_with_meta_and_commit]
+/// along with the decoded value, ie. for deduplication or lag calculation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageMeta {
+    pub partition: i32,
+    pub offset: i64,
+    /// Timestamp in milliseconds since epoch, if available.
+    pub timestamp: Option<i64>,
+    pub key: Option<Vec<u8>>,
+}
+
+impl MessageMeta {
+    pub fn from_message<M: Message>(message: &M) -> Self {
+        Self {
+            partition: message.partition(),
+            offset: message.offset(),
+            timestamp: message.timestamp().to_millis(),
+            key: message.key().map(|key| key.to_vec()),
+        }
+    }
+
+    /// Key as UTF-8 string. Return `None` if there is no key or it is not valid UTF-8.
+    pub fn key_str(&self) -> Option<&str> {
+        self.key
+            .as_deref()
+            .and_then(|key| std::str::from_utf8(key).ok())
+    }
+}
+
+/// Correlation id from `x-correlation-id` header of the message, if any.
+#[cfg(feature = "telemetry")]
+pub fn correlation_id<M: Message>(message: &M) -> Option<String> {
+    let headers = message.headers()?;
+
+    (0..headers.count())
+        .filter_map(|index| headers.get(index))
+        .find(|(key, _)| *key == crate::telemetry::correlation::CORRELATION_ID_HEADER)
+        .and_then(|(_, value)| std::str::from_utf8(value).ok())
+        .map(|value| value.to_string())
+}
+
+#[cfg(feature = "telemetry")]
+async fn with_message_correlation_id<M, Fut>(message: &M, future: Fut) -> Fut::Output
+where
+    M: Message,
+    Fut: Future,
+{
+    match correlation_id(message) {
+        Some(id) => crate::telemetry::correlation::with_correlation_id(id, future).await,
+        None => future.await,
+    }
+}
+
+#[cfg(not(feature = "telemetry"))]
+async fn with_message_correlation_id<M, Fut>(_message: &M, future: Fut) -> Fut::Output
+where
+    M: Message,
+    Fut: Future,
+{
+    future.await
+}
+
+/// When to commit offsets of processed messages, see [ConsumerExt::consume_protobuf].
+/// Committing less often is faster, but more messages are reprocessed after a crash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommitStrategy {
+    EveryMessage,
+    /// Commit once every `n` processed messages. `EveryN(0)` behaves like `EveryMessage`.
+    EveryN(usize),
+    /// Commit on the first processed message after `interval` since last commit.
+    EveryInterval(Duration),
+}
+
+/// Track messages processed since last commit and tell when to commit per [CommitStrategy].
+/// Useful to implement consume loops other than [ConsumerExt::consume_protobuf].
+#[derive(Clone, Debug)]
+pub struct CommitTracker {
+    strategy: CommitStrategy,
+    pending: usize,
+    last_commit: Instant,
+}
+
+impl CommitTracker {
+    pub fn new(strategy: CommitStrategy) -> Self {
+        Self {
+            strategy,
+            pending: 0,
+            last_commit: Instant::now(),
+        }
+    }
+
+    /// Record a processed message. Return `true` if stored offsets should be committed now,
+    /// in which case the tracker is reset as if they were.
+    pub fn record(&mut self) -> bool {
+        self.record_at(Instant::now())
+    }
+
+    /// Whether some processed messages are not committed yet.
+    pub fn has_pending(&self) -> bool {
+        self.pending > 0
+    }
+
+    fn record_at(&mut self, now: Instant) -> bool {
+        self.pending += 1;
+
+        let due = match self.strategy {
+            CommitStrategy::EveryMessage => true,
+            CommitStrategy::EveryN(n) => self.pending >= n,
+            CommitStrategy::EveryInterval(interval) => {
+                now.duration_since(self.last_commit) >= interval
+            }
+        };
+
+        if due {
+            self.pending = 0;
+            self.last_commit = now;
+        }
+
+        due
+    }
+}
+
 #[async_trait]
 pub trait ConsumerExt<C = DefaultConsumerContext>: Consumer<C>
 where
     C: ConsumerContext,
 {
+    /// Decode message into protobuf `T`, process it with `process_fn` then commit the message.
+    ///
+    /// Commit only happens after `process_fn` resolves successfully. With [CommitMode::Async],
+    /// the commit is enqueued and this function returns without waiting for broker acknowledgement.
+    /// If side effects of `process_fn` must be durable before commit, see [ConsumerExt::process_protobuf_then].
     async fn process_protobuf_and_commit<F, T, Fut, E>(
         &self,
         message: Result<BorrowedMessage<'_>, KafkaError>,
@@ -96,11 +381,62 @@ where
     {
         let message = message?;
 
-        set_trace(&message).unwrap_or_else(|err| warn!("set trace fail with error `{}`", err));
+        handle_protobuf(&message, process_fn).await?;
+
+        self.commit_message(&message, mode)?;
+
+        Ok(())
+    }
+
+    /// Like [ConsumerExt::process_protobuf_and_commit], but `process_fn` also receives
+    /// [MessageMeta] of the message.
+    async fn process_protobuf_with_meta_and_commit<F, T, Fut, E>(
+        &self,
+        message: Result<BorrowedMessage<'_>, KafkaError>,
+        process_fn: F,
+        mode: CommitMode,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T, MessageMeta) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        let message = message?;
 
-        let decoded_message = decode_protobuf::<T>(&message)?;
+        handle_protobuf_with_meta(&message, process_fn).await?;
 
-        process_fn(decoded_message)
+        self.commit_message(&message, mode)?;
+
+        Ok(())
+    }
+
+    /// Like [ConsumerExt::process_protobuf_and_commit], but wait for `flush_fn` to resolve
+    /// successfully after `process_fn` and before commit. Useful to flush downstream writes,
+    /// making them durable before the offset is committed.
+    ///
+    /// If `flush_fn` fails, the message is not committed.
+    async fn process_protobuf_then<F, T, Fut, E, F2, Fut2, E2>(
+        &self,
+        message: Result<BorrowedMessage<'_>, KafkaError>,
+        process_fn: F,
+        flush_fn: F2,
+        mode: CommitMode,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+        F2: FnOnce() -> Fut2 + Send,
+        Fut2: Future<Output = Result<(), E2>> + Send,
+        E2: Display,
+    {
+        let message = message?;
+
+        handle_protobuf(&message, process_fn).await?;
+
+        flush_fn()
             .await
             .map_err(|err| KakfaProcessError::ProcessError(err.to_string()))?;
 
@@ -108,9 +444,580 @@ where
 
         Ok(())
     }
+
+    /// Like [ConsumerExt::process_protobuf_and_commit], but decode a message produced with
+    /// [VersionedProtobufMessage](super::VersionedProtobufMessage) by its schema version.
+    /// Unsupported versions fail with [KakfaProcessError::UnsupportedVersion] and are not
+    /// committed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use prost::{DecodeError, Message};
+    /// # use avantis_utils::kafka::VersionedProtobuf;
+    /// # use avantis_utils::kafka::consumer::{CommitMode, ConsumerExt, StreamConsumer};
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct GreetingV1 {
+    /// #     #[prost(string, tag = "1")]
+    /// #     message: String,
+    /// # }
+    /// # enum Greeting {
+    /// #     V1(GreetingV1),
+    /// # }
+    /// # impl VersionedProtobuf for Greeting {
+    /// #     fn decode_version(version: u8, payload: &[u8]) -> Option<Result<Self, DecodeError>> {
+    /// #         (version == 1).then(|| GreetingV1::decode(payload).map(Greeting::V1))
+    /// #     }
+    /// # }
+    /// # async fn example(consumer: StreamConsumer) -> anyhow::Result<()> {
+    /// let message = consumer.recv().await;
+    /// consumer
+    ///     .process_versioned_protobuf_and_commit(
+    ///         message,
+    ///         |greeting: Greeting| async move {
+    ///             match greeting {
+    ///                 Greeting::V1(greeting) => println!("{}", greeting.message),
+    ///             }
+    ///             Ok::<(), anyhow::Error>(())
+    ///         },
+    ///         CommitMode::Async,
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn process_versioned_protobuf_and_commit<F, T, Fut, E>(
+        &self,
+        message: Result<BorrowedMessage<'_>, KafkaError>,
+        process_fn: F,
+        mode: CommitMode,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: VersionedProtobuf,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        let message = message?;
+
+        handle_versioned_protobuf(&message, process_fn).await?;
+
+        self.commit_message(&message, mode)?;
+
+        Ok(())
+    }
+
+    async fn process_bytes_and_commit<F, Fut, E>(
+        &self,
+        message: Result<BorrowedMessage<'_>, KafkaError>,
+        process_fn: F,
+        mode: CommitMode,
+    ) -> Result<(), KakfaProcessError>
+    where
+        F: Fn(&[u8]) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        let message = message?;
+
+        handle_bytes(&message, process_fn).await?;
+
+        self.commit_message(&message, mode)?;
+
+        Ok(())
+    }
+
+    /// Stream of messages decoded into protobuf `T`. Nothing is committed, commit each
+    /// [DecodedMessage::message] after its value is processed.
+    ///
+    /// Messages that fail to decode are yielded as [KakfaProcessError::DecodeError] or
+    /// [KakfaProcessError::EmptyPayload]. They are not committed either, committing any later
+    /// message of the same partition moves past them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use avantis_utils::kafka::consumer::{CommitMode, Consumer, ConsumerExt, StreamConsumer};
+    /// # use futures_lite::StreamExt;
+    /// # use tracing::Instrument;
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct Greeting {
+    /// #     #[prost(string, tag = "1")]
+    /// #     message: String,
+    /// # }
+    /// # async fn example(consumer: StreamConsumer) -> anyhow::Result<()> {
+    /// let mut stream = consumer.protobuf_stream::<Greeting>();
+    /// while let Some(decoded) = stream.next().await {
+    ///     let decoded = decoded?;
+    ///     async { println!("{}", decoded.value.message) }
+    ///         .instrument(decoded.span.clone())
+    ///         .await;
+    ///     consumer.commit_message(&decoded.message, CommitMode::Async)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn protobuf_stream<T>(&self) -> ProtobufStream<'_, T>
+    where
+        T: prost::Message + Default + 'static;
+
+    /// Consume messages decoded into protobuf `T` from [ConsumerExt::protobuf_stream] and
+    /// process them one by one with `process_fn`, until the stream ends. Offset of each processed
+    /// message is stored, then committed with `mode` according to `strategy`.
+    ///
+    /// Messages that fail to decode are logged and skipped. If `process_fn` fails, offsets
+    /// stored so far are committed and the error is returned, the failed message is not.
+    /// Create the consumer with [KafkaConfig::consumer_config_manual_store].
+    /// [CommitStrategy::EveryInterval] is checked as messages are processed, so offsets stay
+    /// pending while no message arrives.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use avantis_utils::kafka::consumer::{CommitMode, CommitStrategy, ConsumerExt, StreamConsumer};
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct Greeting {
+    /// #     #[prost(string, tag = "1")]
+    /// #     message: String,
+    /// # }
+    /// # async fn example(consumer: StreamConsumer) -> anyhow::Result<()> {
+    /// consumer
+    ///     .consume_protobuf(
+    ///         |greeting: Greeting| async move {
+    ///             println!("{}", greeting.message);
+    ///             Ok::<(), anyhow::Error>(())
+    ///         },
+    ///         CommitStrategy::EveryInterval(Duration::from_secs(5)),
+    ///         CommitMode::Async,
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn consume_protobuf<F, T, Fut, E>(
+        &self,
+        process_fn: F,
+        strategy: CommitStrategy,
+        mode: CommitMode,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default + 'static,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        self.consume_protobuf_until(process_fn, strategy, mode, std::future::pending())
+            .await
+    }
+
+    /// Like [ConsumerExt::consume_protobuf], but stop once `shutdown` completes, ie. on
+    /// `SIGTERM` during deploys. The message being processed is finished and its offset
+    /// committed with [CommitMode::Sync] before returning, so it is not processed again
+    /// after restart.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use avantis_utils::kafka::consumer::{CommitMode, CommitStrategy, ConsumerExt, StreamConsumer};
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct Greeting {
+    /// #     #[prost(string, tag = "1")]
+    /// #     message: String,
+    /// # }
+    /// # use std::future::Future;
+    /// # async fn example(
+    /// #     consumer: StreamConsumer,
+    /// #     shutdown_signal: impl Future<Output = ()> + Send,
+    /// # ) -> anyhow::Result<()> {
+    /// // ie. `async { tokio::signal::ctrl_c().await.ok(); }`
+    /// consumer
+    ///     .consume_protobuf_until(
+    ///         |greeting: Greeting| async move {
+    ///             println!("{}", greeting.message);
+    ///             Ok::<(), anyhow::Error>(())
+    ///         },
+    ///         CommitStrategy::EveryN(100),
+    ///         CommitMode::Async,
+    ///         shutdown_signal,
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn consume_protobuf_until<F, T, Fut, E, S>(
+        &self,
+        process_fn: F,
+        strategy: CommitStrategy,
+        mode: CommitMode,
+        shutdown: S,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default + 'static,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+        S: Future<Output = ()> + Send,
+    {
+        consume_stream(
+            self.protobuf_stream::<T>(),
+            |message| self.store_offset_for(message),
+            |mode| self.commit_stored(mode),
+            process_fn,
+            strategy,
+            mode,
+            shutdown,
+            false,
+        )
+        .await
+    }
+
+    /// Store offset of `message` to be committed later by [ConsumerExt::commit_stored].
+    ///
+    /// Together they allow processing a batch of messages, storing each offset after it is
+    /// processed, then committing once per batch. This is still at-least-once, as only offsets
+    /// of processed messages are stored. Require `enable.auto.offset.store=false`, which is set
+    /// by [KafkaConfig::consumer_config_manual_store].
+    fn store_offset_for(&self, message: &BorrowedMessage<'_>) -> KafkaResult<()> {
+        self.store_offset_from_message(message)
+    }
+
+    /// Commit offsets stored by [ConsumerExt::store_offset_for]. Nothing stored since last
+    /// commit is not an error.
+    fn commit_stored(&self, mode: CommitMode) -> KafkaResult<()> {
+        ignore_no_offset(self.commit_consumer_state(mode))
+    }
+
+    /// Rewind every assigned partition to the earliest offset whose timestamp is at or after
+    /// `timestamp_ms` (milliseconds since epoch). Partitions without any message after
+    /// `timestamp_ms` are seeked to the end.
+    ///
+    /// Seek only applies to partitions being consumed, so call this after partitions are
+    /// assigned, ie. after the first message is received or from a rebalance callback.
+    #[instrument(skip_all, name = "kafka::seek_to_timestamp", fields(timestamp_ms = timestamp_ms))]
+    fn seek_to_timestamp<T>(&self, timestamp_ms: i64, timeout: T) -> KafkaResult<()>
+    where
+        T: Into<Timeout>,
+        Self: Sized,
+    {
+        let timeout = timeout.into();
+
+        let mut timestamps = TopicPartitionList::new();
+        for elem in self.assignment()?.elements() {
+            timestamps.add_partition_offset(
+                elem.topic(),
+                elem.partition(),
+                Offset::Offset(timestamp_ms),
+            )?;
+        }
+
+        let offsets = self.offsets_for_times(timestamps, timeout)?;
+        for (topic, partition, offset) in seek_offsets(&offsets)? {
+            debug!("seek {} [{}] to {:?}", topic, partition, offset);
+            self.seek(&topic, partition, offset, timeout)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ConsumerContext + 'static, R> ConsumerExt<C> for StreamConsumer<C, R> {
+    fn protobuf_stream<T>(&self) -> ProtobufStream<'_, T>
+    where
+        T: prost::Message + Default + 'static,
+    {
+        Box::pin(
+            self.stream()
+                .map(|message| decode_message::<_, T>(message.map_err(KakfaProcessError::from)?)),
+        )
+    }
+}
+
+impl KafkaConfig {
+    /// Subscribe to `topics` as `group_id` and consume them with
+    /// [ConsumerExt::consume_protobuf], rebuilding the consumer when it stops because of
+    /// connection loss, ie. the stream ends or fails with a retryable error while brokers
+    /// restart. Reconnects wait per [RetryPolicy::delay] of `reconnect`.
+    ///
+    /// Give up after `reconnect.max_attempts` consecutive reconnects without processing any
+    /// message and return the last result. Errors of `process_fn` and non retryable errors are
+    /// returned right away, like [ConsumerExt::consume_protobuf].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use avantis_utils::kafka::KafkaConfig;
+    /// # use avantis_utils::kafka::consumer::{CommitMode, CommitStrategy};
+    /// # use avantis_utils::util::retry::RetryPolicy;
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct Greeting {
+    /// #     #[prost(string, tag = "1")]
+    /// #     message: String,
+    /// # }
+    /// # async fn example(config: KafkaConfig) -> anyhow::Result<()> {
+    /// let reconnect = RetryPolicy {
+    ///     max_attempts: u32::MAX,
+    ///     max_delay: Duration::from_secs(30),
+    ///     ..RetryPolicy::default()
+    /// };
+    ///
+    /// config
+    ///     .consume_protobuf_reconnecting(
+    ///         "my-group",
+    ///         &["greetings"],
+    ///         |greeting: Greeting| async move {
+    ///             println!("{}", greeting.message);
+    ///             Ok::<(), anyhow::Error>(())
+    ///         },
+    ///         CommitStrategy::EveryN(100),
+    ///         CommitMode::Async,
+    ///         &reconnect,
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip_all, name = "kafka::consume_reconnecting", fields(group = group_id, ?topics))]
+    pub async fn consume_protobuf_reconnecting<F, T, Fut, E>(
+        &self,
+        group_id: &str,
+        topics: &[&str],
+        process_fn: F,
+        strategy: CommitStrategy,
+        mode: CommitMode,
+        reconnect: &RetryPolicy,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default + 'static,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        let process_fn = &process_fn;
+
+        consume_reconnecting(reconnect, move || async move {
+            let processed = AtomicBool::new(false);
+            let result = async {
+                let consumer: StreamConsumer = self.consumer_config_manual_store(group_id)?;
+                consumer.subscribe(topics)?;
+
+                consume_stream(
+                    consumer.protobuf_stream(),
+                    |message| consumer.store_offset_for(message),
+                    |mode| consumer.commit_stored(mode),
+                    |value: T| {
+                        processed.store(true, Ordering::Relaxed);
+                        process_fn(value)
+                    },
+                    strategy,
+                    mode,
+                    std::future::pending(),
+                    true,
+                )
+                .await
+            }
+            .await;
+
+            (processed.load(Ordering::Relaxed), result)
+        })
+        .await
+    }
+}
+
+/// Consume loop of [ConsumerExt::consume_protobuf_until] over decoded messages of `stream`,
+/// storing and committing offsets with `store` and `commit`.
+///
+/// Retryable stream errors are logged and skipped like decode errors, as librdkafka recovers
+/// from them. With `stop_on_connection_loss`, they are returned after committing instead, so
+/// [KafkaConfig::consume_protobuf_reconnecting] could rebuild the consumer.
+#[allow(clippy::too_many_arguments)]
+async fn consume_stream<M, T, St, P, C, F, Fut, E, S>(
+    mut stream: St,
+    store: P,
+    commit: C,
+    process_fn: F,
+    strategy: CommitStrategy,
+    mode: CommitMode,
+    shutdown: S,
+    stop_on_connection_loss: bool,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message,
+    St: Stream<Item = Result<DecodedMessage<M, T>, KakfaProcessError>> + Unpin,
+    P: Fn(&M) -> KafkaResult<()>,
+    C: Fn(CommitMode) -> KafkaResult<()>,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+    S: Future<Output = ()>,
+{
+    let mut tracker = CommitTracker::new(strategy);
+    let mut shutdown = Box::pin(shutdown);
+
+    loop {
+        // check shutdown first, so a busy topic can't delay it.
+        let next = futures_lite::future::or(
+            async {
+                shutdown.as_mut().await;
+                None
+            },
+            async { Some(stream.next().await) },
+        )
+        .await;
+
+        let decoded = match next {
+            Some(Some(decoded)) => decoded,
+            Some(None) => break,
+            None => {
+                info!("shutting down consumer, committing processed messages");
+                commit(CommitMode::Sync)?;
+                return Ok(());
+            }
+        };
+
+        let decoded = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) if stop_on_connection_loss && err.is_retryable() => {
+                if let Err(commit_err) = commit(mode) {
+                    warn!("unable to commit processed messages: {}", commit_err);
+                }
+                return Err(err);
+            }
+            Err(err) => {
+                process_error(err);
+                continue;
+            }
+        };
+
+        #[cfg(feature = "kafka-metrics")]
+        let started_at = std::time::Instant::now();
+
+        let result = with_message_correlation_id(&decoded.message, process_fn(decoded.value))
+            .instrument(decoded.span)
+            .await;
+
+        #[cfg(feature = "kafka-metrics")]
+        super::metrics::record_consume(
+            decoded.message.topic(),
+            started_at.elapsed(),
+            result.is_ok(),
+        );
+
+        if let Err(err) = result {
+            commit(mode)?;
+            return Err(KakfaProcessError::ProcessError(err.to_string()));
+        }
+
+        store(&decoded.message)?;
+        if tracker.record() {
+            commit(mode)?;
+        }
+    }
+
+    commit(mode)?;
+
+    Ok(())
+}
+
+/// Run `consume` again with a new consumer while it stops because of connection loss, see
+/// [KafkaConfig::consume_protobuf_reconnecting]. `consume` returns whether any message was
+/// processed, with the result of its consume loop.
+async fn consume_reconnecting<C, Fut>(
+    reconnect: &RetryPolicy,
+    mut consume: C,
+) -> Result<(), KakfaProcessError>
+where
+    C: FnMut() -> Fut,
+    Fut: Future<Output = (bool, Result<(), KakfaProcessError>)>,
+{
+    let max_attempts = reconnect.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let (processed, result) = consume().await;
+
+        if processed {
+            attempt = 1;
+        }
+        if attempt >= max_attempts || !is_connection_loss(&result) {
+            return result;
+        }
+
+        let delay = reconnect.delay(attempt);
+        match &result {
+            Ok(()) => warn!(attempt, ?delay, "consumer stream ended, reconnecting"),
+            Err(err) => warn!(attempt, ?delay, "consumer failed, reconnecting: {}", err),
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Whether the consume loop stopped because of connection loss, so reconnecting may resume it.
+fn is_connection_loss(result: &Result<(), KakfaProcessError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) => err.is_retryable(),
+    }
 }
 
-impl<C: ConsumerContext, R> ConsumerExt<C> for StreamConsumer<C, R> {}
+/// Partition list to assign, see [KafkaConfig::assigned_consumer].
+fn assignment_list(assignments: &[(&str, i32, Offset)]) -> KafkaResult<TopicPartitionList> {
+    let mut list = TopicPartitionList::with_capacity(assignments.len());
+    for (topic, partition, offset) in assignments {
+        let invalid = |reason: &str| {
+            KafkaError::Subscription(format!(
+                "invalid assignment {} [{}] at {:?}: {}",
+                topic, partition, offset, reason
+            ))
+        };
+
+        if *partition < 0 {
+            return Err(invalid("negative partition"));
+        }
+        match offset {
+            Offset::Beginning | Offset::End | Offset::Stored => {}
+            Offset::Offset(offset) | Offset::OffsetTail(offset) if *offset >= 0 => {}
+            _ => return Err(invalid("unsupported offset")),
+        }
+        if list.find_partition(topic, *partition).is_some() {
+            return Err(invalid("duplicated partition"));
+        }
+
+        list.add_partition_offset(topic, *partition, *offset)?;
+    }
+
+    Ok(list)
+}
+
+fn ignore_no_offset(result: KafkaResult<()>) -> KafkaResult<()> {
+    match result {
+        Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {
+            debug!("no stored offset to commit");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Offsets to seek to from result of `offsets_for_times`, as `(topic, partition, offset)`.
+/// librdkafka returns [Offset::End] for partitions without any message after the timestamp.
+fn seek_offsets(offsets: &TopicPartitionList) -> KafkaResult<Vec<(String, i32, Offset)>> {
+    offsets
+        .elements()
+        .into_iter()
+        .map(|elem| {
+            elem.error()?;
+
+            let offset = match elem.offset() {
+                Offset::Offset(offset) => Offset::Offset(offset),
+                _ => Offset::End,
+            };
+
+            Ok((elem.topic().to_string(), elem.partition(), offset))
+        })
+        .collect()
+}
 
 pub async fn process_protobuf<F, T, Fut, E>(
     message: Result<BorrowedMessage<'_>, KafkaError>,
@@ -124,7 +1031,7 @@ where
 {
     let message = message?;
 
-    let decoded_message = decode_protobuf::<T>(&message)?;
+    let decoded_message = decode_protobuf::<T, _>(&message)?;
 
     process_fn(decoded_message)
         .await
@@ -133,6 +1040,116 @@ where
     Ok(())
 }
 
+/// Set trace, decode and process message. Shared by [ConsumerExt] implementations.
+pub(crate) async fn handle_protobuf<M, F, T, Fut, E>(
+    message: &M,
+    process_fn: F,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message + Sync,
+    T: prost::Message + Default,
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    handle_protobuf_with_meta(message, |value, _| process_fn(value)).await
+}
+
+/// Like [handle_protobuf], passing [MessageMeta] of message to `process_fn`.
+pub(crate) async fn handle_protobuf_with_meta<M, F, T, Fut, E>(
+    message: &M,
+    process_fn: F,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message + Sync,
+    T: prost::Message + Default,
+    F: Fn(T, MessageMeta) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    handle_decoded(message, decode_protobuf::<T, M>, process_fn).await
+}
+
+/// Like [handle_protobuf], decoding by schema version with [VersionedProtobuf].
+pub(crate) async fn handle_versioned_protobuf<M, F, T, Fut, E>(
+    message: &M,
+    process_fn: F,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message + Sync,
+    T: VersionedProtobuf,
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    handle_decoded(message, decode_versioned_protobuf::<T, M>, |value, _| {
+        process_fn(value)
+    })
+    .await
+}
+
+async fn handle_decoded<M, D, F, T, Fut, E>(
+    message: &M,
+    decode_fn: D,
+    process_fn: F,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message + Sync,
+    D: Fn(&M) -> Result<T, KakfaProcessError>,
+    F: Fn(T, MessageMeta) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    set_trace(message).unwrap_or_else(|err| warn!("set trace fail with error `{}`", err));
+
+    #[cfg(feature = "kafka-metrics")]
+    let started_at = std::time::Instant::now();
+
+    let result = match decode_fn(message) {
+        Ok(decoded_message) => with_message_correlation_id(
+            message,
+            process_fn(decoded_message, MessageMeta::from_message(message)),
+        )
+        .await
+        .map_err(|err| KakfaProcessError::ProcessError(err.to_string())),
+        Err(err) => Err(err),
+    };
+
+    #[cfg(feature = "kafka-metrics")]
+    super::metrics::record_consume(message.topic(), started_at.elapsed(), result.is_ok());
+
+    result
+}
+
+/// Set trace and process raw payload of message. Shared by [ConsumerExt] implementations.
+pub(crate) async fn handle_bytes<M, F, Fut, E>(
+    message: &M,
+    process_fn: F,
+) -> Result<(), KakfaProcessError>
+where
+    M: Message + Sync,
+    F: Fn(&[u8]) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    set_trace(message).unwrap_or_else(|err| warn!("set trace fail with error `{}`", err));
+
+    #[cfg(feature = "kafka-metrics")]
+    let started_at = std::time::Instant::now();
+
+    let result = match message.payload() {
+        Some(payload) => with_message_correlation_id(message, process_fn(payload))
+            .await
+            .map_err(|err| KakfaProcessError::ProcessError(err.to_string())),
+        None => Err(KakfaProcessError::EmptyPayload),
+    };
+
+    #[cfg(feature = "kafka-metrics")]
+    super::metrics::record_consume(message.topic(), started_at.elapsed(), result.is_ok());
+
+    result
+}
+
 pub fn process_error(error: KakfaProcessError) {
     warn!(
         "consume and process kafka message fail with error `{}`",
@@ -141,9 +1158,10 @@ pub fn process_error(error: KakfaProcessError) {
 }
 
 #[allow(clippy::unnecessary_lazy_evaluations)]
-fn decode_protobuf<T>(message: &BorrowedMessage<'_>) -> Result<T, KakfaProcessError>
+fn decode_protobuf<T, M>(message: &M) -> Result<T, KakfaProcessError>
 where
     T: prost::Message + Default,
+    M: Message,
 {
     let payload = message
         .payload()
@@ -152,6 +1170,21 @@ where
     Ok(T::decode(payload)?)
 }
 
+fn decode_versioned_protobuf<T, M>(message: &M) -> Result<T, KakfaProcessError>
+where
+    T: VersionedProtobuf,
+    M: Message,
+{
+    let (version, payload) = message
+        .payload()
+        .and_then(split_version)
+        .ok_or_else(|| KakfaProcessError::EmptyPayload)?;
+
+    T::decode_version(version, payload)
+        .ok_or(KakfaProcessError::UnsupportedVersion(version))?
+        .map_err(KakfaProcessError::from)
+}
+
 #[derive(Error, Debug)]
 pub enum KakfaProcessError {
     #[error("kafka error: {0}")]
@@ -166,8 +1199,79 @@ pub enum KakfaProcessError {
     ParseHeaderError(String),
     #[error("any error: {0}")]
     ProcessError(String),
+    #[error("unsupported schema version: {0}")]
+    UnsupportedVersion(u8),
+}
+
+impl KakfaProcessError {
+    /// Whether the error is transient, ie. broker unavailable, leader election or timeout,
+    /// so processing the message again may succeed. Decode and parse errors are not, as well
+    /// as [KakfaProcessError::ProcessError] whose cause is unknown.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::kafka::consumer::KakfaProcessError;
+    /// # use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+    /// let err = KakfaProcessError::from(KafkaError::MessageConsumption(
+    ///     RDKafkaErrorCode::NotLeaderForPartition,
+    /// ));
+    ///
+    /// assert!(err.is_retryable());
+    /// assert!(!KakfaProcessError::EmptyPayload.is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            KakfaProcessError::KafkaError(err) => is_retryable_kafka_error(err),
+            _ => false,
+        }
+    }
 }
 
+/// Whether `err` is transient, see [KakfaProcessError::is_retryable].
+pub fn is_retryable_kafka_error(err: &KafkaError) -> bool {
+    match err {
+        KafkaError::Transaction(err) => err.is_retriable(),
+        err => err
+            .rdkafka_error_code()
+            .is_some_and(is_retryable_error_code),
+    }
+}
+
+fn is_retryable_error_code(code: RDKafkaErrorCode) -> bool {
+    matches!(
+        code,
+        // client side
+        RDKafkaErrorCode::BrokerTransportFailure
+            | RDKafkaErrorCode::Resolve
+            | RDKafkaErrorCode::MessageTimedOut
+            | RDKafkaErrorCode::AllBrokersDown
+            | RDKafkaErrorCode::OperationTimedOut
+            | RDKafkaErrorCode::QueueFull
+            | RDKafkaErrorCode::TimedOutQueue
+            | RDKafkaErrorCode::WaitingForCoordinator
+            | RDKafkaErrorCode::Retry
+            // broker side
+            | RDKafkaErrorCode::LeaderNotAvailable
+            | RDKafkaErrorCode::NotLeaderForPartition
+            | RDKafkaErrorCode::RequestTimedOut
+            | RDKafkaErrorCode::BrokerNotAvailable
+            | RDKafkaErrorCode::ReplicaNotAvailable
+            | RDKafkaErrorCode::NetworkException
+            | RDKafkaErrorCode::CoordinatorLoadInProgress
+            | RDKafkaErrorCode::CoordinatorNotAvailable
+            | RDKafkaErrorCode::NotCoordinator
+            | RDKafkaErrorCode::NotEnoughReplicas
+            | RDKafkaErrorCode::NotEnoughReplicasAfterAppend
+            | RDKafkaErrorCode::RebalanceInProgress
+            | RDKafkaErrorCode::KafkaStorageError
+            | RDKafkaErrorCode::FencedLeaderEpoch
+            | RDKafkaErrorCode::UnknownLeaderEpoch
+    )
+}
+
+/// Consumer context logging rebalances and commits. See
+/// [KafkaConfig::consumer_config_with_context].
 pub struct LoggingConsumerContext;
 
 impl ClientContext for LoggingConsumerContext {}
@@ -208,3 +1312,387 @@ impl ConsumerContext for LoggingConsumerContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TestMessage {
+        #[prost(string, tag = "1")]
+        message: String,
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let kafka_error = |err: KafkaError| KakfaProcessError::from(err).is_retryable();
+
+        assert!(kafka_error(KafkaError::MessageConsumption(
+            RDKafkaErrorCode::NotLeaderForPartition
+        )));
+        assert!(kafka_error(KafkaError::MessageConsumption(
+            RDKafkaErrorCode::AllBrokersDown
+        )));
+        assert!(kafka_error(KafkaError::ConsumerCommit(
+            RDKafkaErrorCode::RebalanceInProgress
+        )));
+        assert!(kafka_error(KafkaError::MessageProduction(
+            RDKafkaErrorCode::MessageTimedOut
+        )));
+
+        assert!(!kafka_error(KafkaError::MessageConsumption(
+            RDKafkaErrorCode::TopicAuthorizationFailed
+        )));
+        assert!(!kafka_error(KafkaError::ConsumerCommit(
+            RDKafkaErrorCode::NoOffset
+        )));
+        assert!(!kafka_error(KafkaError::Canceled));
+
+        assert!(!KakfaProcessError::from(DecodeError::new("invalid")).is_retryable());
+        assert!(!KakfaProcessError::ParseHeaderError("invalid".to_string()).is_retryable());
+        assert!(!KakfaProcessError::ProcessError("failed".to_string()).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_consumer_config_with_context() {
+        let config = KafkaConfig::builder()
+            .brokers_csv("localhost:9092")
+            .security_protocol("plaintext")
+            .build()
+            .unwrap();
+
+        let consumer: KafkaResult<StreamConsumer<LoggingConsumerContext>> =
+            config.consumer_config_with_context("my-group", LoggingConsumerContext);
+
+        assert!(consumer.is_ok());
+        assert_eq!(
+            Some("my-group"),
+            config.consumer_client_config("my-group").get("group.id")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consumer_config_manual_store() {
+        let config = KafkaConfig::builder()
+            .brokers_csv("localhost:9092")
+            .security_protocol("plaintext")
+            .build()
+            .unwrap();
+
+        let consumer: KafkaResult<StreamConsumer> = config.consumer_config_manual_store("my-group");
+
+        assert!(consumer.is_ok());
+        assert_eq!(
+            Some("false"),
+            config
+                .manual_store_client_config("my-group")
+                .get("enable.auto.offset.store")
+        );
+        assert_eq!(
+            None,
+            config
+                .consumer_client_config("my-group")
+                .get("enable.auto.offset.store")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assigned_consumer() {
+        let config = KafkaConfig::builder()
+            .brokers_csv("localhost:9092")
+            .security_protocol("plaintext")
+            .build()
+            .unwrap();
+
+        let consumer: StreamConsumer = config
+            .assigned_consumer(
+                "my-group",
+                &[
+                    ("orders", 0, Offset::Stored),
+                    ("orders", 1, Offset::Offset(42)),
+                    ("payments", 0, Offset::Beginning),
+                ],
+            )
+            .unwrap();
+
+        let assignment = consumer.assignment().unwrap();
+        assert_eq!(3, assignment.count());
+        assert_eq!(
+            Offset::Offset(42),
+            assignment.find_partition("orders", 1).unwrap().offset()
+        );
+        assert_eq!(
+            Offset::Beginning,
+            assignment.find_partition("payments", 0).unwrap().offset()
+        );
+    }
+
+    #[test]
+    fn test_assignment_list() {
+        let list = assignment_list(&[
+            ("orders", 0, Offset::Stored),
+            ("orders", 1, Offset::End),
+            ("orders", 2, Offset::OffsetTail(10)),
+            ("orders", 3, Offset::Offset(0)),
+        ])
+        .unwrap();
+
+        assert_eq!(4, list.count());
+        assert_eq!(
+            Offset::OffsetTail(10),
+            list.find_partition("orders", 2).unwrap().offset()
+        );
+
+        let invalid = |assignment: (&str, i32, Offset)| {
+            assignment_list(&[("orders", 0, Offset::Stored), assignment])
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(invalid(("orders", 1, Offset::Offset(-1))).ends_with("unsupported offset"));
+        assert!(invalid(("orders", 1, Offset::Invalid)).ends_with("unsupported offset"));
+        assert!(invalid(("orders", -1, Offset::Stored)).ends_with("negative partition"));
+        assert!(invalid(("orders", 0, Offset::End)).ends_with("duplicated partition"));
+    }
+
+    #[test]
+    fn test_is_connection_loss() {
+        assert!(is_connection_loss(&Ok(())));
+        assert!(is_connection_loss(&Err(KakfaProcessError::from(
+            KafkaError::MessageConsumption(RDKafkaErrorCode::AllBrokersDown)
+        ))));
+
+        assert!(!is_connection_loss(&Err(KakfaProcessError::ProcessError(
+            "failed".to_string()
+        ))));
+        assert!(!is_connection_loss(&Err(KakfaProcessError::from(
+            KafkaError::MessageConsumption(RDKafkaErrorCode::TopicAuthorizationFailed)
+        ))));
+    }
+
+    #[tokio::test]
+    async fn test_consume_reconnecting() {
+        let reconnect = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_millis(50),
+            jitter: 0.0,
+        };
+        let connection_lost = || {
+            KakfaProcessError::from(KafkaError::MessageConsumption(
+                RDKafkaErrorCode::BrokerTransportFailure,
+            ))
+        };
+
+        // Rebuild once after connection loss, stop on process error.
+        let attempts = &AtomicU32::new(0);
+        let started_at = Instant::now();
+
+        let err = consume_reconnecting(&reconnect, move || async move {
+            match attempts.fetch_add(1, Ordering::Relaxed) {
+                0 => (false, Err(connection_lost())),
+                _ => (
+                    true,
+                    Err(KakfaProcessError::ProcessError("failed".to_string())),
+                ),
+            }
+        })
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, KakfaProcessError::ProcessError(_)));
+        assert_eq!(2, attempts.load(Ordering::Relaxed));
+        assert!(started_at.elapsed() >= Duration::from_millis(50));
+
+        // Give up after max attempts without processing any message.
+        let attempts = &AtomicU32::new(0);
+        let started_at = Instant::now();
+
+        let err = consume_reconnecting(&reconnect, move || async move {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            (false, Err(connection_lost()))
+        })
+        .await
+        .unwrap_err();
+
+        assert!(err.is_retryable());
+        assert_eq!(3, attempts.load(Ordering::Relaxed));
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_commit_tracker() {
+        let start = Instant::now();
+
+        let mut tracker = CommitTracker::new(CommitStrategy::EveryMessage);
+        assert!(tracker.record_at(start));
+        assert!(!tracker.has_pending());
+
+        let mut tracker = CommitTracker::new(CommitStrategy::EveryN(3));
+        assert!(!tracker.record_at(start));
+        assert!(!tracker.record_at(start));
+        assert!(tracker.has_pending());
+        assert!(tracker.record_at(start));
+        assert!(!tracker.has_pending());
+        assert!(!tracker.record_at(start));
+
+        let mut tracker = CommitTracker::new(CommitStrategy::EveryN(0));
+        assert!(tracker.record_at(start));
+
+        let interval = Duration::from_secs(5);
+        let mut tracker = CommitTracker {
+            strategy: CommitStrategy::EveryInterval(interval),
+            pending: 0,
+            last_commit: start,
+        };
+        assert!(!tracker.record_at(start + Duration::from_secs(1)));
+        assert!(tracker.record_at(start + interval));
+        assert!(!tracker.record_at(start + interval + Duration::from_secs(1)));
+        assert!(tracker.record_at(start + interval * 2));
+    }
+
+    #[test]
+    fn test_decode_message() {
+        use prost::Message as _;
+        use rdkafka::message::OwnedMessage;
+        use rdkafka::Timestamp;
+
+        let message = |payload: Option<Vec<u8>>| {
+            OwnedMessage::new(
+                payload,
+                None,
+                "topic".to_string(),
+                Timestamp::NotAvailable,
+                0,
+                42,
+                None,
+            )
+        };
+        let payload = TestMessage {
+            message: "hello".to_string(),
+        }
+        .encode_to_vec();
+
+        let decoded = decode_message::<_, TestMessage>(message(Some(payload))).unwrap();
+
+        assert_eq!("hello", decoded.value.message);
+        assert_eq!(42, decoded.message.offset());
+
+        assert!(matches!(
+            decode_message::<_, TestMessage>(message(None)),
+            Err(KakfaProcessError::EmptyPayload)
+        ));
+        assert!(matches!(
+            decode_message::<_, TestMessage>(message(Some(vec![0xff]))),
+            Err(KakfaProcessError::DecodeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_consume_stream_retryable_error() {
+        use std::sync::Mutex;
+
+        use rdkafka::message::OwnedMessage;
+        use rdkafka::Timestamp;
+
+        let decoded = |offset: i64| {
+            Ok(DecodedMessage {
+                message: OwnedMessage::new(
+                    None,
+                    None,
+                    "topic".to_string(),
+                    Timestamp::NotAvailable,
+                    0,
+                    offset,
+                    None,
+                ),
+                value: TestMessage {
+                    message: offset.to_string(),
+                },
+                span: tracing::Span::none(),
+            })
+        };
+        let messages = || {
+            futures_lite::stream::iter(vec![
+                decoded(1),
+                Err(KakfaProcessError::from(KafkaError::MessageConsumption(
+                    RDKafkaErrorCode::BrokerTransportFailure,
+                ))),
+                decoded(2),
+            ])
+        };
+
+        let consume = |stop_on_connection_loss: bool| {
+            let processed = Mutex::new(Vec::new());
+            let stored = Mutex::new(Vec::new());
+            let result = futures_lite::future::block_on(consume_stream(
+                messages(),
+                |message: &OwnedMessage| {
+                    stored.lock().unwrap().push(message.offset());
+                    Ok(())
+                },
+                |_| Ok(()),
+                |value: TestMessage| {
+                    processed.lock().unwrap().push(value.message);
+                    async { Ok::<_, anyhow::Error>(()) }
+                },
+                CommitStrategy::EveryMessage,
+                CommitMode::Async,
+                std::future::pending(),
+                stop_on_connection_loss,
+            ));
+            (
+                result,
+                processed.into_inner().unwrap(),
+                stored.into_inner().unwrap(),
+            )
+        };
+
+        let (result, processed, stored) = consume(false);
+        assert!(result.is_ok());
+        assert_eq!(vec!["1", "2"], processed);
+        assert_eq!(vec![1, 2], stored);
+
+        let (result, processed, stored) = consume(true);
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(vec!["1"], processed);
+        assert_eq!(vec![1], stored);
+    }
+
+    #[test]
+    fn test_ignore_no_offset() {
+        assert!(ignore_no_offset(Ok(())).is_ok());
+        assert!(
+            ignore_no_offset(Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset))).is_ok()
+        );
+        assert!(ignore_no_offset(Err(KafkaError::ConsumerCommit(
+            RDKafkaErrorCode::RebalanceInProgress
+        )))
+        .is_err());
+    }
+
+    #[test]
+    fn test_seek_offsets() {
+        let mut offsets = TopicPartitionList::new();
+        offsets
+            .add_partition_offset("topic", 0, Offset::Offset(42))
+            .unwrap();
+        offsets
+            .add_partition_offset("topic", 1, Offset::End)
+            .unwrap();
+        offsets
+            .add_partition_offset("topic", 2, Offset::Invalid)
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                ("topic".to_string(), 0, Offset::Offset(42)),
+                ("topic".to_string(), 1, Offset::End),
+                ("topic".to_string(), 2, Offset::End),
+            ],
+            seek_offsets(&offsets).unwrap()
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! OpenTelemetry metrics for kafka consumers and producers. Require `kafka-metrics` feature.
+//!
+//! Instruments are created from the global meter on first record. If no meter provider
+//! is installed, a no-op meter is used and nothing is exported. Install the meter provider
+//! before consuming or producing the first message.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use opentelemetry::global;
+use opentelemetry::metrics::{Counter, ValueRecorder};
+use opentelemetry::KeyValue;
+
+struct KafkaMetrics {
+    consumed: Counter<u64>,
+    process_duration: ValueRecorder<f64>,
+    produced: Counter<u64>,
+}
+
+fn metrics() -> &'static KafkaMetrics {
+    static METRICS: OnceLock<KafkaMetrics> = OnceLock::new();
+
+    METRICS.get_or_init(|| {
+        let meter = global::meter("avantis-utils");
+
+        KafkaMetrics {
+            consumed: meter
+                .u64_counter("kafka.consumer.messages")
+                .with_description("Number of consumed kafka messages")
+                .init(),
+            process_duration: meter
+                .f64_value_recorder("kafka.consumer.process_duration")
+                .with_description("Processing duration of consumed kafka messages in milliseconds")
+                .init(),
+            produced: meter
+                .u64_counter("kafka.producer.messages")
+                .with_description("Number of produced kafka messages")
+                .init(),
+        }
+    })
+}
+
+fn attributes(topic: &str, success: bool) -> [KeyValue; 2] {
+    [
+        KeyValue::new("topic", topic.to_string()),
+        KeyValue::new("status", if success { "success" } else { "failure" }),
+    ]
+}
+
+pub(crate) fn record_consume(topic: &str, duration: Duration, success: bool) {
+    let attributes = attributes(topic, success);
+    let metrics = metrics();
+
+    metrics.consumed.add(1, &attributes);
+    metrics
+        .process_duration
+        .record(duration.as_secs_f64() * 1000.0, &attributes);
+}
+
+pub(crate) fn record_produce(topic: &str, success: bool) {
+    metrics().produced.add(1, &attributes(topic, success));
+}
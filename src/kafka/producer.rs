@@ -1,17 +1,24 @@
 use std::collections::HashMap;
+use std::ops::Deref;
+use std::time::Duration;
 
 use anyhow::Error;
 use opentelemetry::global;
-use rdkafka::config::FromClientConfig;
-use rdkafka::error::{KafkaError, KafkaResult};
+use rdkafka::config::{FromClientConfig, RDKafkaLogLevel};
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
 use rdkafka::message::{OwnedHeaders, OwnedMessage};
+use rdkafka::producer::Producer;
+use rdkafka::types::RDKafkaConfRes;
+use rdkafka::util::{AsyncRuntime, DefaultRuntime};
 use rdkafka::ClientConfig;
 use tracing::instrument;
 use tracing::warn;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use super::KafkaConfig;
+use super::ProtobufKafkaRecord;
 
+pub use rdkafka::producer::future_producer::OwnedDeliveryResult;
 pub use rdkafka::producer::{FutureProducer, FutureRecord};
 pub use rdkafka::util::Timeout;
 
@@ -21,6 +28,110 @@ pub fn with_trace_header(
     Ok(record.headers(create_tracing_header()))
 }
 
+/// Send protobuf record with trace header. Record produce metrics if `kafka-metrics` feature is enabled.
+pub async fn send_protobuf(
+    producer: &FutureProducer,
+    record: &ProtobufKafkaRecord<'_>,
+    queue_timeout: impl Into<Timeout>,
+) -> OwnedDeliveryResult {
+    let future_record = FutureRecord::from(record).headers(create_tracing_header());
+
+    let result = producer.send(future_record, queue_timeout).await;
+
+    #[cfg(feature = "kafka-metrics")]
+    super::metrics::record_produce(record.topic, result.is_ok());
+
+    result
+}
+
+/// Send protobuf record like [send_protobuf] and wait for its delivery report, up to `timeout`
+/// in total. Return `(partition, offset)` assigned by the broker.
+///
+/// On timeout, fail with [RDKafkaErrorCode::MessageTimedOut]. The message may still be delivered
+/// later, so treat it as unknown rather than lost, ie. don't commit consumer offsets.
+pub async fn send_protobuf_and_confirm(
+    producer: &FutureProducer,
+    record: &ProtobufKafkaRecord<'_>,
+    timeout: Duration,
+) -> KafkaResult<(i32, i64)> {
+    futures_lite::future::or(
+        async {
+            send_protobuf(producer, record, timeout)
+                .await
+                .map_err(|(err, _)| err)
+        },
+        async {
+            DefaultRuntime::delay_for(timeout).await;
+            Err(KafkaError::MessageProduction(
+                RDKafkaErrorCode::MessageTimedOut,
+            ))
+        },
+    )
+    .await
+}
+
+/// Wait for every message in flight of `producer` to be delivered, up to `timeout`. Fail with
+/// [RDKafkaErrorCode::OperationTimedOut] if some are still in flight.
+///
+/// This blocks the current thread, call it from a blocking context in async code,
+/// ie. `tokio::task::spawn_blocking`.
+pub fn flush(producer: &FutureProducer, timeout: impl Into<Timeout>) -> KafkaResult<()> {
+    producer.flush(timeout);
+
+    match producer.in_flight_count() {
+        0 => Ok(()),
+        count => {
+            warn!("{} kafka messages still in flight after flush", count);
+            Err(KafkaError::MessageProduction(
+                RDKafkaErrorCode::OperationTimedOut,
+            ))
+        }
+    }
+}
+
+/// [FutureProducer] which flushes on drop for `flush_duration_millis` of its [KafkaConfig],
+/// so messages sent without [flush] are not silently lost. Messages still in flight after
+/// that are logged. Deref to [FutureProducer].
+///
+/// Dropping it blocks the current thread like [flush].
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::kafka::KafkaConfig;
+/// # use avantis_utils::kafka::producer::FutureRecord;
+/// # async fn example(config: KafkaConfig) -> rdkafka::error::KafkaResult<()> {
+/// let producer = config.flushing_producer()?;
+///
+/// producer
+///     .send_result(FutureRecord::to("topic").key("key").payload("value"))
+///     .map_err(|(err, _)| err)?;
+///
+/// // flushed here
+/// drop(producer);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FlushingProducer {
+    producer: FutureProducer,
+    flush_timeout: Duration,
+}
+
+impl Deref for FlushingProducer {
+    type Target = FutureProducer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.producer
+    }
+}
+
+impl Drop for FlushingProducer {
+    fn drop(&mut self) {
+        // messages still in flight are logged by flush.
+        let _ = flush(&self.producer, self.flush_timeout);
+    }
+}
+
 fn create_tracing_header() -> OwnedHeaders {
     let cx = tracing::Span::current().context();
     let mut trace_metadata = HashMap::new();
@@ -42,27 +153,82 @@ fn create_tracing_header() -> OwnedHeaders {
         warn!("trace metadata don't have tracestate");
     }
 
+    #[cfg(feature = "telemetry")]
+    if let Some(correlation_id) = crate::telemetry::correlation::current_correlation_id() {
+        headers = headers.add(
+            crate::telemetry::correlation::CORRELATION_ID_HEADER,
+            &correlation_id,
+        );
+    }
+
     headers
 }
 
+const DEFAULT_MESSAGE_TIMEOUT_MS: u64 = 30000;
+const DEFAULT_LOG_LEVEL: RDKafkaLogLevel = RDKafkaLogLevel::Warning;
+
 impl KafkaConfig {
+    /// Create a producer with brokers and security settings of this config, and
+    /// `message_timeout_millis`, `acks` and `log_level` if set.
     #[instrument(skip_all, name = "kafka::init_producer", fields(brokers = %self.brokers_csv))]
     pub fn producer_config<T>(&self) -> KafkaResult<T>
     where
         T: FromClientConfig,
     {
-        ClientConfig::new()
-            .set("bootstrap.servers", &self.brokers_csv)
-            .set("message.timeout.ms", "30000")
+        self.producer_client_config()?.create()
+    }
+
+    fn producer_client_config(&self) -> KafkaResult<ClientConfig> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", self.brokers().join(","))
             .set(
-                "security.protocol",
-                self.security_protocol
-                    .clone()
-                    .unwrap_or_else(|| "ssl".to_string()),
+                "message.timeout.ms",
+                self.message_timeout_millis
+                    .unwrap_or(DEFAULT_MESSAGE_TIMEOUT_MS)
+                    .to_string(),
             )
-            .set_log_level(rdkafka::config::RDKafkaLogLevel::Debug)
+            .set("security.protocol", self.security_protocol())
             // .set("log.connection.close", "false")
-            .create()
+            .set_log_level(self.producer_log_level()?);
+
+        if let Some(acks) = &self.acks {
+            config.set("acks", acks);
+        }
+
+        Ok(config)
+    }
+
+    fn producer_log_level(&self) -> KafkaResult<RDKafkaLogLevel> {
+        let log_level = match &self.log_level {
+            Some(log_level) => log_level,
+            None => return Ok(DEFAULT_LOG_LEVEL),
+        };
+
+        match log_level.to_lowercase().as_str() {
+            "emerg" => Ok(RDKafkaLogLevel::Emerg),
+            "alert" => Ok(RDKafkaLogLevel::Alert),
+            "critical" => Ok(RDKafkaLogLevel::Critical),
+            "error" => Ok(RDKafkaLogLevel::Error),
+            "warning" | "warn" => Ok(RDKafkaLogLevel::Warning),
+            "notice" => Ok(RDKafkaLogLevel::Notice),
+            "info" => Ok(RDKafkaLogLevel::Info),
+            "debug" => Ok(RDKafkaLogLevel::Debug),
+            _ => Err(KafkaError::ClientConfig(
+                RDKafkaConfRes::RD_KAFKA_CONF_INVALID,
+                "Invalid log level".to_string(),
+                "log_level".to_string(),
+                log_level.clone(),
+            )),
+        }
+    }
+
+    /// Create a [FlushingProducer] with [KafkaConfig::producer_config].
+    pub fn flushing_producer(&self) -> KafkaResult<FlushingProducer> {
+        Ok(FlushingProducer {
+            producer: self.producer_config()?,
+            flush_timeout: Duration::from_millis(self.flush_duration_millis),
+        })
     }
 }
 
@@ -73,3 +239,114 @@ pub fn process_error((error, message): (KafkaError, OwnedMessage)) -> (i32, i64)
     );
     (-1, -1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::ProtobufKafkaMessage;
+    use super::*;
+
+    fn unreachable_config() -> KafkaConfig {
+        KafkaConfig::builder()
+            .brokers_csv("localhost:1")
+            .flush_duration_millis(100)
+            .security_protocol("plaintext")
+            .build()
+            .unwrap()
+    }
+
+    fn unreachable_producer() -> FutureProducer {
+        unreachable_config().producer_config().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_protobuf_and_confirm_timeout() {
+        let producer = unreachable_producer();
+        let record = ProtobufKafkaRecord {
+            topic: "topic",
+            message: ProtobufKafkaMessage {
+                key: "key".to_string(),
+                value: "value".into(),
+            },
+        };
+
+        let result =
+            send_protobuf_and_confirm(&producer, &record, Duration::from_millis(100)).await;
+
+        assert!(matches!(
+            result,
+            Err(KafkaError::MessageProduction(
+                RDKafkaErrorCode::MessageTimedOut
+            ))
+        ));
+
+        let result = flush(&producer, Duration::from_millis(100));
+
+        assert!(matches!(
+            result,
+            Err(KafkaError::MessageProduction(
+                RDKafkaErrorCode::OperationTimedOut
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flushing_producer_flushes_on_drop() {
+        let producer = unreachable_config().flushing_producer().unwrap();
+
+        let _delivery = producer
+            .send_result(FutureRecord::to("topic").key("key").payload("value"))
+            .map_err(|(err, _)| err)
+            .unwrap();
+        assert!(producer.in_flight_count() > 0);
+
+        let started_at = std::time::Instant::now();
+        drop(producer);
+
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_producer_config_settings() {
+        let config = KafkaConfig {
+            message_timeout_millis: Some(5000),
+            acks: Some("1".to_string()),
+            ..unreachable_config()
+        };
+
+        let client_config = config.producer_client_config().unwrap();
+
+        assert_eq!(Some("5000"), client_config.get("message.timeout.ms"));
+        assert_eq!(Some("1"), client_config.get("acks"));
+        assert!(matches!(client_config.log_level, RDKafkaLogLevel::Warning));
+
+        let client_config = unreachable_config().producer_client_config().unwrap();
+
+        assert_eq!(Some("30000"), client_config.get("message.timeout.ms"));
+        assert_eq!(None, client_config.get("acks"));
+    }
+
+    #[test]
+    fn test_producer_log_level() {
+        let config = |log_level: &str| KafkaConfig {
+            log_level: Some(log_level.to_string()),
+            ..unreachable_config()
+        };
+
+        assert!(matches!(
+            unreachable_config().producer_log_level().unwrap(),
+            RDKafkaLogLevel::Warning
+        ));
+        assert!(matches!(
+            config("DEBUG").producer_log_level().unwrap(),
+            RDKafkaLogLevel::Debug
+        ));
+        assert!(matches!(
+            config("error").producer_log_level().unwrap(),
+            RDKafkaLogLevel::Error
+        ));
+        assert!(matches!(
+            config("verbose").producer_config::<FutureProducer>(),
+            Err(KafkaError::ClientConfig(..))
+        ));
+    }
+}
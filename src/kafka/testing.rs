@@ -0,0 +1,637 @@
+//! Test utilities for kafka consumers. Require `test-util` feature.
+//!
+//! [InMemoryConsumer] mirrors [ConsumerExt](super::consumer::ConsumerExt) methods over an
+//! in-process queue of [OwnedMessage], so consumer logic can be tested without a broker.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Mutex;
+
+use rdkafka::message::{OwnedHeaders, OwnedMessage};
+use rdkafka::{Message, Timestamp};
+
+use super::consumer::{
+    handle_bytes, handle_protobuf, handle_protobuf_with_meta, handle_versioned_protobuf,
+    process_error, CommitStrategy, CommitTracker, KakfaProcessError, MessageMeta,
+};
+use super::{ProtobufKafkaRecord, VersionedProtobuf};
+
+/// In-memory consumer for tests.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::kafka::testing::InMemoryConsumer;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let consumer = InMemoryConsumer::new();
+/// consumer.send_bytes("topic", "key", b"payload".to_vec());
+///
+/// let message = consumer.recv().unwrap();
+/// consumer
+///     .process_bytes_and_commit(message, |payload| {
+///         assert_eq!(b"payload", payload);
+///         async { Ok::<(), anyhow::Error>(()) }
+///     })
+///     .await
+///     .unwrap();
+///
+/// assert_eq!(vec![("topic".to_string(), 0, 0)], consumer.committed());
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryConsumer {
+    messages: Mutex<VecDeque<OwnedMessage>>,
+    committed: Mutex<Vec<(String, i32, i64)>>,
+    stored: Mutex<Vec<(String, i32, i64)>>,
+    next_offset: Mutex<i64>,
+}
+
+impl InMemoryConsumer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enqueue a message as is.
+    pub fn send(&self, message: OwnedMessage) {
+        self.messages.lock().unwrap().push_back(message);
+    }
+
+    /// Enqueue a message with raw payload. Offset is incremented per message, partition is always `0`.
+    pub fn send_bytes(&self, topic: &str, key: &str, payload: Vec<u8>) {
+        let offset = {
+            let mut next_offset = self.next_offset.lock().unwrap();
+            let offset = *next_offset;
+            *next_offset += 1;
+            offset
+        };
+
+        self.send(OwnedMessage::new(
+            Some(payload),
+            Some(key.as_bytes().to_vec()),
+            topic.to_string(),
+            Timestamp::NotAvailable,
+            0,
+            offset,
+            Some(OwnedHeaders::new()),
+        ));
+    }
+
+    /// Enqueue a protobuf encoded message. See [InMemoryConsumer::send_bytes].
+    pub fn send_protobuf<T: prost::Message>(&self, topic: &str, key: &str, message: &T) {
+        self.send_bytes(topic, key, message.encode_to_vec());
+    }
+
+    /// Enqueue a record as built for producers, ie. to check that consumers decode what
+    /// producers encode. See [InMemoryConsumer::send_bytes].
+    pub fn send_record(&self, record: &ProtobufKafkaRecord<'_>) {
+        self.send_bytes(
+            record.topic,
+            &record.message.key,
+            record.message.value.to_vec(),
+        );
+    }
+
+    /// Dequeue next message. Return `None` if there is no message left.
+    pub fn recv(&self) -> Option<OwnedMessage> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    /// Committed messages as `(topic, partition, offset)`, in commit order.
+    pub fn committed(&self) -> Vec<(String, i32, i64)> {
+        self.committed.lock().unwrap().clone()
+    }
+
+    pub async fn process_protobuf_and_commit<F, T, Fut, E>(
+        &self,
+        message: OwnedMessage,
+        process_fn: F,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        handle_protobuf(&message, process_fn).await?;
+
+        self.commit(&message);
+
+        Ok(())
+    }
+
+    pub async fn process_protobuf_with_meta_and_commit<F, T, Fut, E>(
+        &self,
+        message: OwnedMessage,
+        process_fn: F,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T, MessageMeta) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        handle_protobuf_with_meta(&message, process_fn).await?;
+
+        self.commit(&message);
+
+        Ok(())
+    }
+
+    pub async fn process_protobuf_then<F, T, Fut, E, F2, Fut2, E2>(
+        &self,
+        message: OwnedMessage,
+        process_fn: F,
+        flush_fn: F2,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+        F2: FnOnce() -> Fut2 + Send,
+        Fut2: Future<Output = Result<(), E2>> + Send,
+        E2: Display,
+    {
+        handle_protobuf(&message, process_fn).await?;
+
+        flush_fn()
+            .await
+            .map_err(|err| KakfaProcessError::ProcessError(err.to_string()))?;
+
+        self.commit(&message);
+
+        Ok(())
+    }
+
+    pub async fn process_versioned_protobuf_and_commit<F, T, Fut, E>(
+        &self,
+        message: OwnedMessage,
+        process_fn: F,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: VersionedProtobuf,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        handle_versioned_protobuf(&message, process_fn).await?;
+
+        self.commit(&message);
+
+        Ok(())
+    }
+
+    pub async fn process_bytes_and_commit<F, Fut, E>(
+        &self,
+        message: OwnedMessage,
+        process_fn: F,
+    ) -> Result<(), KakfaProcessError>
+    where
+        F: Fn(&[u8]) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        handle_bytes(&message, process_fn).await?;
+
+        self.commit(&message);
+
+        Ok(())
+    }
+
+    /// Process every enqueued message like
+    /// [ConsumerExt::consume_protobuf](super::consumer::ConsumerExt::consume_protobuf),
+    /// committing according to `strategy`.
+    pub async fn consume_protobuf<F, T, Fut, E>(
+        &self,
+        process_fn: F,
+        strategy: CommitStrategy,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        self.consume_protobuf_until(process_fn, strategy, std::future::pending())
+            .await
+    }
+
+    /// Like [InMemoryConsumer::consume_protobuf], but stop before the next message once
+    /// `shutdown` completes, like
+    /// [ConsumerExt::consume_protobuf_until](super::consumer::ConsumerExt::consume_protobuf_until).
+    pub async fn consume_protobuf_until<F, T, Fut, E, S>(
+        &self,
+        process_fn: F,
+        strategy: CommitStrategy,
+        shutdown: S,
+    ) -> Result<(), KakfaProcessError>
+    where
+        T: prost::Message + Default,
+        F: Fn(T) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+        S: Future<Output = ()>,
+    {
+        let mut tracker = CommitTracker::new(strategy);
+        let mut shutdown = Box::pin(shutdown);
+
+        loop {
+            if futures_lite::future::poll_once(shutdown.as_mut())
+                .await
+                .is_some()
+            {
+                break;
+            }
+
+            let message = match self.recv() {
+                Some(message) => message,
+                None => break,
+            };
+
+            match handle_protobuf(&message, &process_fn).await {
+                Ok(()) => {}
+                Err(KakfaProcessError::ProcessError(err)) => {
+                    self.commit_stored();
+                    return Err(KakfaProcessError::ProcessError(err));
+                }
+                Err(err) => {
+                    process_error(err);
+                    continue;
+                }
+            }
+
+            self.store_offset_for(&message);
+            if tracker.record() {
+                self.commit_stored();
+            }
+        }
+
+        self.commit_stored();
+
+        Ok(())
+    }
+
+    /// Store offset of `message`, committed later by [InMemoryConsumer::commit_stored].
+    pub fn store_offset_for(&self, message: &OwnedMessage) {
+        self.stored.lock().unwrap().push(message_offset(message));
+    }
+
+    /// Commit offsets stored by [InMemoryConsumer::store_offset_for], in store order.
+    pub fn commit_stored(&self) {
+        let stored = std::mem::take(&mut *self.stored.lock().unwrap());
+        self.committed.lock().unwrap().extend(stored);
+    }
+
+    fn commit(&self, message: &OwnedMessage) {
+        self.committed.lock().unwrap().push(message_offset(message));
+    }
+}
+
+fn message_offset(message: &OwnedMessage) -> (String, i32, i64) {
+    (
+        message.topic().to_string(),
+        message.partition(),
+        message.offset(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TestMessage {
+        #[prost(string, tag = "1")]
+        message: String,
+    }
+
+    #[tokio::test]
+    async fn test_process_protobuf_and_commit() {
+        let consumer = InMemoryConsumer::new();
+        consumer.send_protobuf(
+            "topic",
+            "key",
+            &TestMessage {
+                message: "hello".to_string(),
+            },
+        );
+        consumer.send_protobuf(
+            "topic",
+            "key",
+            &TestMessage {
+                message: "fail".to_string(),
+            },
+        );
+
+        let process_fn = |message: TestMessage| async move {
+            match message.message.as_str() {
+                "hello" => Ok(()),
+                _ => Err("unable to process"),
+            }
+        };
+
+        consumer
+            .process_protobuf_and_commit(consumer.recv().unwrap(), process_fn)
+            .await
+            .unwrap();
+
+        let err = consumer
+            .process_protobuf_and_commit(consumer.recv().unwrap(), process_fn)
+            .await
+            .unwrap_err();
+
+        assert_eq!("any error: unable to process", err.to_string());
+        assert_eq!(vec![("topic".to_string(), 0, 0)], consumer.committed());
+        assert!(consumer.recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_process_protobuf_with_meta_and_commit() {
+        let consumer = InMemoryConsumer::new();
+        consumer.send_bytes("topic", "skipped", vec![]);
+        consumer.recv().unwrap();
+        consumer.send_protobuf(
+            "topic",
+            "key",
+            &TestMessage {
+                message: "hello".to_string(),
+            },
+        );
+
+        consumer
+            .process_protobuf_with_meta_and_commit(
+                consumer.recv().unwrap(),
+                |message: TestMessage, meta: MessageMeta| async move {
+                    assert_eq!("hello", message.message);
+                    assert_eq!(0, meta.partition);
+                    assert_eq!(1, meta.offset);
+                    assert_eq!(None, meta.timestamp);
+                    assert_eq!(Some("key"), meta.key_str());
+                    Ok::<(), anyhow::Error>(())
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(vec![("topic".to_string(), 0, 1)], consumer.committed());
+    }
+
+    #[tokio::test]
+    async fn test_consume_protobuf() {
+        let consumer = InMemoryConsumer::new();
+        for message in ["a", "b", "c", "d", "e"] {
+            consumer.send_protobuf(
+                "topic",
+                "key",
+                &TestMessage {
+                    message: message.to_string(),
+                },
+            );
+        }
+
+        let committed = std::sync::Mutex::new(vec![]);
+        consumer
+            .consume_protobuf(
+                |_: TestMessage| {
+                    committed.lock().unwrap().push(consumer.committed().len());
+                    async { Ok::<(), anyhow::Error>(()) }
+                },
+                CommitStrategy::EveryN(2),
+            )
+            .await
+            .unwrap();
+
+        // committed count seen before processing each message.
+        assert_eq!(vec![0, 0, 2, 2, 4], *committed.lock().unwrap());
+        assert_eq!(5, consumer.committed().len());
+
+        consumer.send_protobuf(
+            "topic",
+            "key",
+            &TestMessage {
+                message: "ok".to_string(),
+            },
+        );
+        consumer.send_protobuf(
+            "topic",
+            "key",
+            &TestMessage {
+                message: "fail".to_string(),
+            },
+        );
+
+        let err = consumer
+            .consume_protobuf(
+                |message: TestMessage| async move {
+                    match message.message.as_str() {
+                        "ok" => Ok(()),
+                        _ => Err("unable to process"),
+                    }
+                },
+                CommitStrategy::EveryN(10),
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!("any error: unable to process", err.to_string());
+        assert_eq!(
+            Some(("topic".to_string(), 0, 5)),
+            consumer.committed().last().cloned()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_protobuf_until() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        let consumer = InMemoryConsumer::new();
+        for message in ["a", "b", "c", "d", "e"] {
+            consumer.send_protobuf(
+                "topic",
+                "key",
+                &TestMessage {
+                    message: message.to_string(),
+                },
+            );
+        }
+
+        let processed = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        consumer
+            .consume_protobuf_until(
+                |_: TestMessage| {
+                    if processed.fetch_add(1, Ordering::SeqCst) == 1 {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    async { Ok::<(), anyhow::Error>(()) }
+                },
+                CommitStrategy::EveryN(10),
+                async {
+                    while !stop.load(Ordering::SeqCst) {
+                        futures_lite::future::yield_now().await;
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(2, processed.load(Ordering::SeqCst));
+        assert_eq!(
+            vec![("topic".to_string(), 0, 0), ("topic".to_string(), 0, 1)],
+            consumer.committed()
+        );
+        assert!(consumer.recv().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_send_record_round_trip() {
+        use super::super::ProtobufKafkaMessage;
+
+        let consumer = InMemoryConsumer::new();
+        consumer.send_record(&ProtobufKafkaRecord {
+            topic: "topic",
+            message: ProtobufKafkaMessage::encode(
+                "key",
+                &TestMessage {
+                    message: "hello".to_string(),
+                },
+            ),
+        });
+
+        let message = consumer.recv().unwrap();
+
+        assert_eq!(Some(b"key".as_ref()), message.key());
+
+        consumer
+            .process_protobuf_and_commit(message, |message: TestMessage| async move {
+                match message.message.as_str() {
+                    "hello" => Ok(()),
+                    _ => Err("unexpected message"),
+                }
+            })
+            .await
+            .unwrap();
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum VersionedTestMessage {
+        V1(TestMessage),
+        V2(String),
+    }
+
+    impl VersionedProtobuf for VersionedTestMessage {
+        fn decode_version(version: u8, payload: &[u8]) -> Option<Result<Self, prost::DecodeError>> {
+            use prost::Message;
+
+            match version {
+                1 => Some(TestMessage::decode(payload).map(VersionedTestMessage::V1)),
+                2 => Some(String::decode(payload).map(VersionedTestMessage::V2)),
+                _ => None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_versioned_protobuf_and_commit() {
+        use super::super::VersionedProtobufMessage;
+
+        let consumer = InMemoryConsumer::new();
+        for message in [
+            VersionedProtobufMessage::new(
+                1,
+                TestMessage {
+                    message: "hello".to_string(),
+                },
+            )
+            .encode("key"),
+            VersionedProtobufMessage::new(2, "hello".to_string()).encode("key"),
+            VersionedProtobufMessage::new(3, "hello".to_string()).encode("key"),
+        ] {
+            consumer.send_record(&ProtobufKafkaRecord {
+                topic: "topic",
+                message,
+            });
+        }
+
+        let received = Mutex::new(vec![]);
+        let process_fn = |message: VersionedTestMessage| {
+            received.lock().unwrap().push(message);
+            async { Ok::<(), anyhow::Error>(()) }
+        };
+
+        for _ in 0..2 {
+            consumer
+                .process_versioned_protobuf_and_commit(consumer.recv().unwrap(), process_fn)
+                .await
+                .unwrap();
+        }
+        let err = consumer
+            .process_versioned_protobuf_and_commit(consumer.recv().unwrap(), process_fn)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            vec![
+                VersionedTestMessage::V1(TestMessage {
+                    message: "hello".to_string(),
+                }),
+                VersionedTestMessage::V2("hello".to_string()),
+            ],
+            received.into_inner().unwrap()
+        );
+        assert!(matches!(err, KakfaProcessError::UnsupportedVersion(3)));
+        assert_eq!(
+            vec![("topic".to_string(), 0, 0), ("topic".to_string(), 0, 1)],
+            consumer.committed()
+        );
+    }
+
+    #[test]
+    fn test_commit_stored() {
+        let consumer = InMemoryConsumer::new();
+        consumer.send_bytes("topic", "key", b"first".to_vec());
+        consumer.send_bytes("topic", "key", b"second".to_vec());
+
+        consumer.store_offset_for(&consumer.recv().unwrap());
+        consumer.store_offset_for(&consumer.recv().unwrap());
+
+        assert!(consumer.committed().is_empty());
+
+        consumer.commit_stored();
+        consumer.commit_stored();
+
+        assert_eq!(
+            vec![("topic".to_string(), 0, 0), ("topic".to_string(), 0, 1)],
+            consumer.committed()
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "telemetry")]
+    async fn test_process_bytes_restores_correlation_id() {
+        use crate::telemetry::correlation::{current_correlation_id, CORRELATION_ID_HEADER};
+
+        let consumer = InMemoryConsumer::new();
+        consumer.send(OwnedMessage::new(
+            Some(b"payload".to_vec()),
+            None,
+            "topic".to_string(),
+            Timestamp::NotAvailable,
+            0,
+            0,
+            Some(OwnedHeaders::new().add(CORRELATION_ID_HEADER, "request-1")),
+        ));
+
+        consumer
+            .process_bytes_and_commit(consumer.recv().unwrap(), |_| async {
+                match current_correlation_id().as_deref() {
+                    Some("request-1") => Ok(()),
+                    _ => Err("correlation id not found"),
+                }
+            })
+            .await
+            .unwrap();
+    }
+}
@@ -12,3 +12,5 @@ pub mod pagination;
 pub mod redis;
 #[cfg(feature = "telemetry")]
 pub mod telemetry;
+#[cfg(feature = "util")]
+pub mod util;
@@ -1,5 +1,16 @@
 pub use avantis_utils_derive::PaginatedQuery;
 
+// Example:
+// uncomment this to try
+//
+// #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+// struct Bar {
+//     #[page(default = 1)]
+//     pub page: Option<i32>,
+//     #[page_size(default = 20)]
+//     pub page_size: Option<i32>,
+// }
+
 // Example:
 // uncomment this to try
 //
@@ -11,9 +22,23 @@ pub use avantis_utils_derive::PaginatedQuery;
 //     pub offset_t: Option<i32>,
 // }
 
+/// Derived implementations never return negative values. Out of range values are clamped to `0`,
+/// or to `min` if given, ie. `#[limit(default = 100, min = 1)]`.
 pub trait PaginatedQuery {
     fn limit(&self) -> i32;
     fn offset(&self) -> i32;
+
+    /// Limit as `i64`. Derived implementations compute it without overflow,
+    /// and [PaginatedQuery::limit] saturates at `i32::MAX`.
+    fn limit_i64(&self) -> i64 {
+        self.limit().into()
+    }
+
+    /// Offset as `i64`. Prefer this for deep pagination where offset may exceed `i32::MAX`.
+    /// Derived implementations saturate at `i64::MAX` instead of overflowing.
+    fn offset_i64(&self) -> i64 {
+        self.offset().into()
+    }
 }
 
 #[cfg(test)]
@@ -35,4 +60,161 @@ mod tests {
         assert_eq!(100, input.limit());
         assert_eq!(0, input.offset());
     }
+
+    #[test]
+    fn test_negative_input() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input {
+            #[limit(default = 100, min = 1)]
+            pub limit_t: Option<i32>,
+            #[offset(default = 0)]
+            pub offset_t: Option<i32>,
+        }
+
+        let input = Input {
+            limit_t: Some(50),
+            offset_t: Some(10),
+        };
+
+        assert_eq!(50, input.limit());
+        assert_eq!(10, input.offset());
+
+        let input = Input {
+            limit_t: Some(-5),
+            offset_t: Some(-10),
+        };
+
+        assert_eq!(1, input.limit());
+        assert_eq!(0, input.offset());
+
+        let input = Input {
+            limit_t: Some(0),
+            offset_t: None,
+        };
+
+        assert_eq!(1, input.limit());
+        assert_eq!(0, input.offset());
+    }
+
+    #[test]
+    fn test_page_input() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input {
+            #[page(default = 1)]
+            pub page: Option<i32>,
+            #[page_size(default = 20)]
+            pub page_size: Option<i32>,
+        }
+
+        let input = Input::default();
+
+        assert_eq!(20, input.limit());
+        assert_eq!(0, input.offset());
+
+        let input = Input {
+            page: Some(3),
+            page_size: Some(50),
+        };
+
+        assert_eq!(50, input.limit());
+        assert_eq!(100, input.offset());
+
+        let input = Input {
+            page: Some(-2),
+            page_size: None,
+        };
+
+        assert_eq!(20, input.limit());
+        assert_eq!(0, input.offset(), "page should be clamped to 1");
+    }
+
+    #[test]
+    fn test_i64_input() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input {
+            #[limit(default = 100)]
+            pub limit: Option<i32>,
+            #[offset(default = 0)]
+            pub offset: Option<i64>,
+        }
+
+        let input = Input {
+            limit: None,
+            offset: Some(5_000_000_000),
+        };
+
+        assert_eq!(100, input.limit_i64());
+        assert_eq!(5_000_000_000, input.offset_i64());
+        assert_eq!(i32::MAX, input.offset());
+    }
+
+    #[test]
+    fn test_page_input_overflow() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input {
+            #[page(default = 1)]
+            pub page: Option<i64>,
+            #[page_size(default = 20)]
+            pub page_size: Option<i32>,
+        }
+
+        let input = Input {
+            page: Some(1_000_001),
+            page_size: Some(10_000),
+        };
+
+        assert_eq!(10_000, input.limit());
+        assert_eq!(10_000_000_000, input.offset_i64());
+        assert_eq!(i32::MAX, input.offset());
+
+        let input = Input {
+            page: Some(i64::MAX),
+            page_size: Some(i32::MAX),
+        };
+
+        assert_eq!(i64::MAX, input.offset_i64());
+    }
+
+    #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+    struct InnerInput {
+        #[limit(default = 20)]
+        pub limit: Option<i32>,
+        #[offset(default = 0)]
+        pub offset: Option<i32>,
+    }
+
+    #[test]
+    fn test_tuple_struct_input() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input(InnerInput);
+
+        let input = Input(InnerInput {
+            limit: None,
+            offset: Some(40),
+        });
+
+        assert_eq!(20, input.limit());
+        assert_eq!(40, input.offset());
+        assert_eq!(20, input.limit_i64());
+        assert_eq!(40, input.offset_i64());
+    }
+
+    #[test]
+    fn test_generic_struct_input() {
+        #[derive(Default, Debug, PartialEq, PaginatedQuery)]
+        struct Input<T>(T);
+
+        let input = Input(InnerInput {
+            limit: Some(10),
+            offset: None,
+        });
+
+        assert_eq!(10, input.limit());
+        assert_eq!(0, input.offset());
+
+        let input = Input(Input(InnerInput::default()));
+
+        assert_eq!(20, input.limit());
+        assert_eq!(0, input.offset());
+    }
 }
@@ -9,18 +9,39 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
+use tokio::task::JoinHandle;
 
 // TODO: tracing error wont works. find a new way to communicate to user that it works or not
 use tracing::error;
 
+pub use cache::Cache;
+pub use cache::WarmReport;
 pub use connection::Connection;
 pub use connection::Pool;
 pub use connection::RedisConfig;
+pub use connection::RedisConfigBuilder;
+#[cfg(feature = "redis-in-memory")]
+pub use in_memory::InMemoryCache;
+pub use metrics::with_cache_name;
+pub use queue::RedisQueue;
+pub use queue::Reserved;
 
-// TODO: add tests for VecRedisValue
+mod cache;
+#[cfg(feature = "redis-in-memory")]
+mod in_memory;
+mod metrics;
+mod queue;
 
+use metrics::CacheEvent;
+
+/// Cache-aside helpers, implemented for Redis connections and `InMemoryCache`.
+///
+/// Since 0.8, [AsyncCommands] is not a supertrait anymore, so it could be implemented by caches
+/// other than Redis. Bound on [RedisGetOrFetchExt] where Redis commands are used as well.
 #[async_trait]
-pub trait GetOrFetchExt: AsyncCommands {
+pub trait GetOrFetchExt {
+    /// Get cached value of `key`, or load it with `data_loader` and cache it for `expire_seconds`.
+    /// `expire_seconds` of `0` means no expiry.
     async fn get_or_fetch<K, V, F, Fut>(
         &mut self,
         key: K,
@@ -32,6 +53,74 @@ pub trait GetOrFetchExt: AsyncCommands {
         V: FromRedisValue + ToRedisArgs + Send + Sync,
         F: FnOnce() -> Fut + Send,
         Fut: Future<Output = anyhow::Result<V>> + Send;
+
+    /// Like [GetOrFetchExt::get_or_fetch], for `Vec<T>` cached as JSON.
+    /// Wrap and unwrap [VecRedisValue] internally.
+    async fn get_or_fetch_vec<K, T, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<Vec<T>>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<Vec<T>>> + Send,
+    {
+        let result: VecRedisValue<T> = self
+            .get_or_fetch(
+                key,
+                || async move { Ok(VecRedisValue(data_loader().await?)) },
+                expire_seconds,
+            )
+            .await?;
+
+        Ok(result.0)
+    }
+
+    /// Like [GetOrFetchExt::get_or_fetch], but return `key` back with the value, ie. to tell
+    /// results apart when fetching many keys concurrently.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::redis::{GetOrFetchExt, Result};
+    /// # async fn example(connection: redis_cluster_async::Connection) -> Result<()> {
+    /// let fetches = ["AAPL", "GOOG"].map(|symbol| {
+    ///     let mut connection = connection.clone();
+    ///     async move {
+    ///         connection
+    ///             .get_or_fetch_keyed(symbol, || async { Ok("100".to_string()) }, 60)
+    ///             .await
+    ///     }
+    /// });
+    ///
+    /// for fetch in fetches {
+    ///     let (symbol, price): (&str, String) = fetch.await?;
+    ///     println!("{} {}", symbol, price);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn get_or_fetch_keyed<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<(K, V)>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        let value = self.get_or_fetch(&key, data_loader, expire_seconds).await?;
+
+        Ok((key, value))
+    }
 }
 
 #[async_trait]
@@ -48,18 +137,26 @@ impl GetOrFetchExt for redis_cluster_async::Connection {
         F: FnOnce() -> Fut + Send,
         Fut: Future<Output = anyhow::Result<V>> + Send,
     {
-        if cfg!(test) {
-            return Ok(data_loader().await?);
-        }
-
         match self.get(&key).await {
-            Ok(Some(bytes)) => Ok(bytes),
+            Ok(Some(bytes)) => {
+                metrics::record(CacheEvent::Hit);
+                Ok(bytes)
+            }
             Ok(None) => {
+                metrics::record(CacheEvent::Miss);
                 let result = data_loader().await?;
-                self.set_ex(&key, &result, expire_seconds).await?;
+                let _: () = match expire_seconds {
+                    NO_EXPIRY => self.set(&key, &result).await?,
+                    _ => self.set_ex(&key, &result, expire_seconds).await?,
+                };
                 Ok(result)
             }
+            Err(err) if is_wrong_type(&err) => {
+                metrics::record(CacheEvent::Error);
+                Err(key_type_mismatch(&key))
+            }
             Err(err) => {
+                metrics::record(CacheEvent::Error);
                 error!("redis error: {:?}", err);
                 Ok(data_loader().await?)
             }
@@ -67,14 +164,48 @@ impl GetOrFetchExt for redis_cluster_async::Connection {
     }
 }
 
+/// [GetOrFetchExt] of a Redis connection, with [AsyncCommands] as a supertrait like
+/// [GetOrFetchExt] before 0.8. Implemented for every type implementing both.
+pub trait RedisGetOrFetchExt: GetOrFetchExt + AsyncCommands {}
+
+impl<T: GetOrFetchExt + AsyncCommands> RedisGetOrFetchExt for T {}
+
+/// Handle of a background refresh spawned by [GetOrRefreshExt]. Resolve once the refreshed
+/// value is stored, or with the error of `data_loader` or Redis.
+pub type RefreshHandle = JoinHandle<Result<()>>;
+
 #[async_trait]
 pub trait GetOrRefreshExt {
+    /// Get cached value of `key`. If it is expired, return it anyway and refresh it with
+    /// `data_loader` in background. `expire_seconds` of `0` means no expiry.
     async fn get_or_refresh<'a, V, F, Fut>(
-        mut self,
+        self,
         key: &str, // Would be nice if key is K: ToRedisArgs + Send + Sync instead.
         data_loader: F,
         expire_seconds: usize,
     ) -> Result<V>
+    where
+        Self: Sized + Send,
+        V: FromRedisValue + ToRedisArgs + Send + Sync + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        let (value, _) = self
+            .get_or_refresh_with_handle(key, data_loader, expire_seconds)
+            .await?;
+
+        Ok(value)
+    }
+
+    /// Like [GetOrRefreshExt::get_or_refresh], but also return the [RefreshHandle] of the
+    /// background refresh if one is spawned. Await it to observe the refresh, ie. in tests
+    /// or to drain in-flight refreshes on shutdown.
+    async fn get_or_refresh_with_handle<'a, V, F, Fut>(
+        mut self,
+        key: &str,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<(V, Option<RefreshHandle>)>
     where
         V: FromRedisValue + ToRedisArgs + Send + Sync + 'static,
         F: FnOnce() -> Fut + Send + 'static,
@@ -83,21 +214,17 @@ pub trait GetOrRefreshExt {
 
 #[async_trait]
 impl GetOrRefreshExt for connection::Connection {
-    async fn get_or_refresh<'a, V, F, Fut>(
+    async fn get_or_refresh_with_handle<'a, V, F, Fut>(
         mut self,
         key: &str,
         data_loader: F,
         expire_seconds: usize,
-    ) -> Result<V>
+    ) -> Result<(V, Option<RefreshHandle>)>
     where
         V: FromRedisValue + ToRedisArgs + Send + Sync + 'static,
         F: FnOnce() -> Fut + Send + 'static,
         Fut: Future<Output = anyhow::Result<V>> + Send,
     {
-        if cfg!(test) {
-            return Ok(data_loader().await?);
-        }
-
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
@@ -107,7 +234,7 @@ impl GetOrRefreshExt for connection::Connection {
         let owned_key = key.to_owned();
         macro_rules! awaiting_get_and_set {
             () => {{
-                let new_expired_when = now + expire_seconds as u64;
+                let new_expired_when = expired_when(now, expire_seconds);
 
                 let new_value = data_loader().await?;
 
@@ -122,44 +249,166 @@ impl GetOrRefreshExt for connection::Connection {
             }};
         }
 
-        let expired_when: Result<Option<u64>> = Ok(self.hget(key, "expired_when").await?);
-        let value: Result<Option<V>> = Ok(self.hget(key, "value").await?);
+        let record_error = |err: RedisError| {
+            metrics::record(CacheEvent::Error);
+            map_key_error(&key, err)
+        };
+        let expired_when: Option<u64> =
+            self.hget(key, "expired_when").await.map_err(record_error)?;
+        let value: Option<V> = self.hget(key, "value").await.map_err(record_error)?;
 
         match (expired_when, value) {
-            (Ok(Some(expired_when)), Ok(Some(value))) if !is_expired(expired_when) => Ok(value),
-            (Ok(Some(_)), Ok(Some(value))) => {
-                tokio::spawn(async move {
-                    if let Err(e) = async { awaiting_get_and_set!() }.await {
+            (Some(expired_when), Some(value)) if !is_expired(expired_when) => {
+                metrics::record(CacheEvent::Hit);
+                Ok((value, None))
+            }
+            (Some(_), Some(value)) => {
+                metrics::record(CacheEvent::BackgroundRefresh);
+                let handle = metrics::spawn_named(async move {
+                    let result = async { awaiting_get_and_set!() }.await.map(|_| ());
+                    if let Err(e) = &result {
                         error!("Failed to load and set in background: {}", e);
                     }
+                    result
                 });
 
-                Ok(value)
+                Ok((value, Some(handle)))
             }
-            (Ok(None), _) | (_, Ok(None)) => {
-                awaiting_get_and_set!()
+            _ => {
+                metrics::record(CacheEvent::Miss);
+                Ok((awaiting_get_and_set!()?, None))
             }
-            (Err(err), _) | (_, Err(err)) => {
-                error!("redis error: {:?}", err);
+        }
+    }
+}
+
+#[async_trait]
+pub trait GetWithTtlExt {
+    /// Get value with its remaining TTL in seconds. Return `None` for missing keys.
+    ///
+    /// Support both layouts. For keys set by [GetOrFetchExt], TTL is from Redis `TTL`,
+    /// `-1` for keys without expiry. For keys set by [GetOrRefreshExt], TTL is the delta
+    /// to `expired_when`, `0` if value is stale and waiting for refresh.
+    async fn get_with_ttl<K, V>(&mut self, key: K) -> Result<Option<(V, i64)>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + Send + Sync;
+}
+
+#[async_trait]
+impl GetWithTtlExt for redis_cluster_async::Connection {
+    async fn get_with_ttl<K, V>(&mut self, key: K) -> Result<Option<(V, i64)>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + Send + Sync,
+    {
+        let key_type: String = redis_rs::cmd("TYPE").arg(&key).query_async(self).await?;
+
+        match key_type.as_str() {
+            "none" => Ok(None),
+            "hash" => {
+                let expired_when: Option<u64> = self.hget(&key, "expired_when").await?;
+                let value: Option<V> = self.hget(&key, "value").await?;
 
-                awaiting_get_and_set!()
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_secs();
+
+                Ok(match (expired_when, value) {
+                    (Some(NEVER_EXPIRED_WHEN), Some(value)) => Some((value, -1)),
+                    (Some(expired_when), Some(value)) => {
+                        Some((value, (expired_when as i64 - now as i64).max(0)))
+                    }
+                    _ => None,
+                })
+            }
+            _ => {
+                let value: Option<V> = self.get(&key).await?;
+                let ttl: i64 = self.ttl(&key).await?;
+
+                // `-2` means key is expired between `GET` and `TTL`.
+                Ok(value.filter(|_| ttl != -2).map(|value| (value, ttl)))
             }
         }
     }
 }
 
+/// `expire_seconds` value meaning no expiry.
+const NO_EXPIRY: usize = 0;
+
+/// Sentinel `expired_when` of values without expiry.
+const NEVER_EXPIRED_WHEN: u64 = u64::MAX;
+
+/// Unix time in seconds when a value cached at `now` is expired.
+fn expired_when(now: u64, expire_seconds: usize) -> u64 {
+    match expire_seconds {
+        NO_EXPIRY => NEVER_EXPIRED_WHEN,
+        _ => now.saturating_add(expire_seconds as u64),
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("data error")]
     Data(#[from] anyhow::Error),
     #[error("redis error")]
-    Redis(#[from] RedisError),
+    Redis(RedisError),
     #[error("cluster connection error")]
-    Cluster(#[from] RunError<RedisError>),
+    Cluster(RunError<RedisError>),
+    #[error("redis authentication failed, check username and password: {0}")]
+    Authentication(RedisError),
+    #[error("redis permission denied, check ACL rules of the user: {0}")]
+    PermissionDenied(RedisError),
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("key `{key}` holds a value of another type, it may be shared by get_or_fetch and get_or_refresh")]
+    KeyTypeMismatch { key: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl From<RedisError> for Error {
+    fn from(err: RedisError) -> Self {
+        if err.kind() == ErrorKind::AuthenticationFailed || err.code() == Some("WRONGPASS") {
+            Error::Authentication(err)
+        } else if err.code() == Some("NOPERM") {
+            Error::PermissionDenied(err)
+        } else {
+            Error::Redis(err)
+        }
+    }
+}
+
+impl From<RunError<RedisError>> for Error {
+    fn from(err: RunError<RedisError>) -> Self {
+        match err {
+            RunError::User(err) => match Error::from(err) {
+                Error::Redis(err) => Error::Cluster(RunError::User(err)),
+                err => err,
+            },
+            err => Error::Cluster(err),
+        }
+    }
+}
+
+fn is_wrong_type(err: &RedisError) -> bool {
+    err.code() == Some("WRONGTYPE")
+}
+
+fn key_type_mismatch<K: ToRedisArgs>(key: &K) -> Error {
+    Error::KeyTypeMismatch {
+        key: String::from_utf8_lossy(&key.to_redis_args().concat()).into_owned(),
+    }
+}
+
+fn map_key_error<K: ToRedisArgs>(key: &K, err: RedisError) -> Error {
+    match is_wrong_type(&err) {
+        true => key_type_mismatch(key),
+        false => err.into(),
+    }
+}
+
 macro_rules! invalid_type_error {
     ($v:expr, $det:expr) => {
         RedisError::from((
@@ -205,8 +454,8 @@ impl<T: Serialize + DeserializeOwned> FromRedisValue for VecRedisValue<T> {
                     invalid_type_error!(
                         v,
                         format!(
-                            "Could not deserialize into {} struct with err {}.",
-                            stringify!($t),
+                            "Could not deserialize into Vec<{}> with err {}.",
+                            std::any::type_name::<T>(),
                             err
                         )
                     )
@@ -215,44 +464,363 @@ impl<T: Serialize + DeserializeOwned> FromRedisValue for VecRedisValue<T> {
             }
             _ => Err(invalid_type_error!(
                 v,
-                format!("Could not deserialize into {} struct.", stringify!($t))
+                format!(
+                    "Could not deserialize into Vec<{}>.",
+                    std::any::type_name::<T>()
+                )
             )),
         }
     }
 }
 
 mod connection {
+    use std::borrow::Cow;
+    use std::fmt;
+    use std::time::Duration;
+
+    use anyhow::anyhow;
     use async_trait::async_trait;
     use bb8_redis::bb8;
     use redis_rs::aio::ConnectionLike;
+    use redis_rs::ConnectionAddr;
+    use redis_rs::ConnectionInfo;
     use redis_rs::IntoConnectionInfo;
     use redis_rs::RedisError;
     use redis_rs::RedisResult;
     use serde::Deserialize;
+    use serde::Deserializer;
 
+    use super::Error;
     use super::Result;
 
     pub type Pool = bb8::Pool<RedisClusterConnectionManager>;
     pub type Connection = bb8::PooledConnection<'static, RedisClusterConnectionManager>;
 
-    #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+    #[derive(Clone, PartialEq, Eq, Deserialize)]
+    #[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
     pub struct RedisConfig {
-        pub hosts_csv: String,
+        /// Cluster hosts. Accept either a list or a comma separated string.
+        /// Also accept legacy `hosts_csv` field name. Each host is either a URL, ie.
+        /// `redis://h1:7000`, or `host[:port]` with port default to `6379`, ie. `h1:7000`.
+        #[serde(alias = "hosts_csv", deserialize_with = "deserialize_hosts")]
+        #[cfg_attr(feature = "config-schema", schemars(schema_with = "hosts_schema"))]
+        pub hosts: Vec<String>,
+        #[serde(default = "default_expire_seconds")]
         pub expire_seconds: usize,
+        #[serde(default = "default_max_connections")]
         pub max_connections: u32,
+        #[serde(default = "default_connection_timeout_seconds")]
+        pub connection_timeout_seconds: u64,
+        /// Username for Redis 6+ ACL auth, sent with two-argument `AUTH`. Override username
+        /// in hosts if set. Require a password.
+        #[serde(default)]
+        pub username: Option<String>,
+        /// Password or auth token. Override password in hosts if set.
+        #[serde(default)]
+        pub password: Option<String>,
+        /// Connect with TLS even if hosts use `redis://` scheme. Require `redis-tls` feature.
+        #[serde(default)]
+        pub use_tls: bool,
+    }
+
+    impl fmt::Debug for RedisConfig {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("RedisConfig")
+                .field("hosts", &self.hosts)
+                .field("expire_seconds", &self.expire_seconds)
+                .field("max_connections", &self.max_connections)
+                .field(
+                    "connection_timeout_seconds",
+                    &self.connection_timeout_seconds,
+                )
+                .field("username", &self.username)
+                .field(
+                    "password",
+                    &self.password.as_ref().map(|_| format_args!("***")),
+                )
+                .field("use_tls", &self.use_tls)
+                .finish()
+        }
+    }
+
+    fn default_expire_seconds() -> usize {
+        3600
+    }
+
+    fn default_max_connections() -> u32 {
+        10
+    }
+
+    fn default_connection_timeout_seconds() -> u64 {
+        30
     }
 
     impl RedisConfig {
-        fn hosts(&self) -> Vec<&str> {
-            self.hosts_csv.split(',').collect()
+        /// Create a [RedisConfigBuilder] for programmatic construction, ie. in tests or tools.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// # use avantis_utils::redis::RedisConfig;
+        /// let config = RedisConfig::builder()
+        ///     .hosts(&["redis://localhost:6379"])
+        ///     .expire_seconds(60)
+        ///     .build()
+        ///     .unwrap();
+        /// ```
+        pub fn builder() -> RedisConfigBuilder {
+            RedisConfigBuilder::default()
+        }
+
+        fn hosts(&self) -> Result<Vec<&str>> {
+            let hosts = self
+                .hosts
+                .iter()
+                .map(|host| host.trim())
+                .collect::<Vec<_>>();
+
+            if hosts.is_empty() {
+                return Err(Error::Config("redis hosts must not be empty".to_string()));
+            }
+
+            if let Some(index) = hosts.iter().position(|host| host.is_empty()) {
+                return Err(Error::Config(format!(
+                    "redis host at index {} must not be blank",
+                    index
+                )));
+            }
+
+            Ok(hosts)
+        }
+
+        pub(super) fn connection_infos(&self) -> Result<Vec<ConnectionInfo>> {
+            if self.use_tls && !cfg!(feature = "redis-tls") {
+                return Err(Error::Config(
+                    "redis use_tls requires `redis-tls` feature".to_string(),
+                ));
+            }
+
+            self.hosts()?
+                .into_iter()
+                .map(|host| {
+                    let mut info = host_url(host).into_connection_info()?;
+
+                    if self.use_tls {
+                        info.addr = match info.addr {
+                            ConnectionAddr::Tcp(host, port) => ConnectionAddr::TcpTls {
+                                host,
+                                port,
+                                insecure: false,
+                            },
+                            addr => addr,
+                        };
+                    }
+
+                    if let Some(username) = &self.username {
+                        info.redis.username = Some(username.clone());
+                    }
+
+                    if let Some(password) = &self.password {
+                        info.redis.password = Some(password.clone());
+                    }
+
+                    if info.redis.username.is_some() && info.redis.password.is_none() {
+                        return Err(Error::Config(
+                            "redis username requires password".to_string(),
+                        ));
+                    }
+
+                    Ok(info)
+                })
+                .collect()
+        }
+
+        fn connection_timeout(&self) -> Duration {
+            Duration::from_secs(self.connection_timeout_seconds)
         }
 
         pub async fn init_pool(&self) -> Result<Pool> {
             Ok(bb8::Pool::builder()
                 .max_size(self.max_connections)
-                .build(RedisClusterConnectionManager::new(self.hosts())?)
+                .connection_timeout(self.connection_timeout())
+                .build(RedisClusterConnectionManager::new(
+                    self.connection_infos()?,
+                )?)
                 .await?)
         }
+
+        /// Like [RedisConfig::init_pool], but check out a connection and `PING` the cluster
+        /// before returning, within `connection_timeout_seconds`. Fail if the cluster is
+        /// unreachable, ie. to fail readiness at startup instead of on the first cache call.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// # use avantis_utils::redis::{RedisConfig, Result};
+        /// # async fn example(config: RedisConfig) -> Result<()> {
+        /// let pool = config.init_pool_validated().await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn init_pool_validated(&self) -> Result<Pool> {
+            let pool = self.init_pool().await?;
+
+            tokio::time::timeout(self.connection_timeout(), async {
+                let mut connection = pool.get().await?;
+                connection
+                    .req_packed_command(&redis_rs::cmd("PING"))
+                    .await
+                    .and_then(check_is_pong)?;
+                Ok::<_, Error>(())
+            })
+            .await
+            .map_err(|_| Error::Cluster(bb8::RunError::TimedOut))??;
+
+            Ok(pool)
+        }
+
+        /// Like [RedisConfig::init_pool], but set up the pool on the runtime of `handle`, ie.
+        /// when called outside of a tokio runtime or from a custom runtime. The pool stays bound
+        /// to that runtime, which must outlive it.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// # use avantis_utils::redis::{RedisConfig, Result};
+        /// # async fn example(config: RedisConfig) -> Result<()> {
+        /// let runtime = tokio::runtime::Builder::new_multi_thread()
+        ///     .enable_all()
+        ///     .build()
+        ///     .unwrap();
+        ///
+        /// let pool = config.init_pool_on(runtime.handle()).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn init_pool_on(&self, handle: &tokio::runtime::Handle) -> Result<Pool> {
+            let config = self.clone();
+
+            handle
+                .spawn(async move { config.init_pool().await })
+                .await
+                .map_err(|err| Error::Data(anyhow!("redis pool setup failed: {}", err)))?
+        }
+
+        /// Open a single cluster connection without a pool. Useful for one-off commands,
+        /// ie. migrations or admin scripts. Prefer [RedisConfig::init_pool] for services.
+        pub async fn connect_one(&self) -> Result<redis_cluster_async::Connection> {
+            let client = redis_cluster_async::Client::open(self.connection_infos()?)?;
+
+            Ok(client.get_connection().await?)
+        }
+    }
+
+    /// Builder for [RedisConfig]. See [RedisConfig::builder]. Default to the same values as
+    /// deserialization.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct RedisConfigBuilder {
+        config: RedisConfig,
+    }
+
+    impl Default for RedisConfigBuilder {
+        fn default() -> Self {
+            Self {
+                config: RedisConfig {
+                    hosts: vec![],
+                    expire_seconds: default_expire_seconds(),
+                    max_connections: default_max_connections(),
+                    connection_timeout_seconds: default_connection_timeout_seconds(),
+                    username: None,
+                    password: None,
+                    use_tls: false,
+                },
+            }
+        }
+    }
+
+    impl RedisConfigBuilder {
+        pub fn hosts<S: AsRef<str>>(mut self, hosts: &[S]) -> Self {
+            self.config.hosts = hosts.iter().map(|host| host.as_ref().to_string()).collect();
+            self
+        }
+
+        pub fn expire_seconds(mut self, expire_seconds: usize) -> Self {
+            self.config.expire_seconds = expire_seconds;
+            self
+        }
+
+        pub fn max_connections(mut self, max_connections: u32) -> Self {
+            self.config.max_connections = max_connections;
+            self
+        }
+
+        pub fn connection_timeout_seconds(mut self, connection_timeout_seconds: u64) -> Self {
+            self.config.connection_timeout_seconds = connection_timeout_seconds;
+            self
+        }
+
+        pub fn username(mut self, username: impl Into<String>) -> Self {
+            self.config.username = Some(username.into());
+            self
+        }
+
+        pub fn password(mut self, password: impl Into<String>) -> Self {
+            self.config.password = Some(password.into());
+            self
+        }
+
+        pub fn use_tls(mut self, use_tls: bool) -> Self {
+            self.config.use_tls = use_tls;
+            self
+        }
+
+        /// Build [RedisConfig]. Fail if no host is given or any host is blank.
+        pub fn build(self) -> Result<RedisConfig> {
+            self.config.hosts()?;
+
+            Ok(self.config)
+        }
+    }
+
+    /// Either a list or a comma separated string, like [deserialize_hosts].
+    #[cfg(feature = "config-schema")]
+    fn hosts_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            subschemas: Some(Box::new(schemars::schema::SubschemaValidation {
+                any_of: Some(vec![
+                    gen.subschema_for::<Vec<String>>(),
+                    gen.subschema_for::<String>(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    /// `host` as a `redis://` URL if it has no scheme, keeping its port if any.
+    fn host_url(host: &str) -> Cow<'_, str> {
+        match host.contains("://") {
+            true => Cow::Borrowed(host),
+            false => Cow::Owned(format!("redis://{}", host)),
+        }
+    }
+
+    fn deserialize_hosts<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Hosts {
+            Csv(String),
+            List(Vec<String>),
+        }
+
+        Ok(match Hosts::deserialize(deserializer)? {
+            Hosts::Csv(csv) => csv.split(',').map(|host| host.trim().to_string()).collect(),
+            Hosts::List(hosts) => hosts,
+        })
     }
 
     pub struct RedisClusterConnectionManager {
@@ -298,3 +866,407 @@ mod connection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redis_get_or_fetch_ext() {
+        fn assert_redis_get_or_fetch_ext<T: RedisGetOrFetchExt>() {}
+
+        assert_redis_get_or_fetch_ext::<redis_cluster_async::Connection>();
+    }
+
+    #[test]
+    fn test_vec_redis_value_error() {
+        let result =
+            VecRedisValue::<i32>::from_redis_value(&redis_rs::Value::Data(b"not json".to_vec()));
+
+        let err = result.err().unwrap().to_string();
+
+        assert!(
+            err.contains("Could not deserialize into Vec<i32> with err"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_map_key_error() {
+        let wrong_type = redis_rs::parse_redis_value(
+            b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n",
+        )
+        .unwrap_err();
+
+        let err = map_key_error(&"key", wrong_type);
+
+        assert!(matches!(err, Error::KeyTypeMismatch { ref key } if key == "key"));
+
+        let err = map_key_error(
+            &"key",
+            RedisError::from((ErrorKind::ResponseError, "some error")),
+        );
+
+        assert!(matches!(err, Error::Redis(_)));
+    }
+
+    #[test]
+    fn test_acl_errors() {
+        let no_permission = redis_rs::parse_redis_value(
+            b"-NOPERM this user has no permissions to run the 'get' command\r\n",
+        )
+        .unwrap_err();
+
+        let err = Error::from(RunError::User(no_permission));
+
+        assert!(matches!(err, Error::PermissionDenied(_)));
+        assert!(err
+            .to_string()
+            .starts_with("redis permission denied, check ACL rules of the user: NOPERM"));
+
+        let wrong_password = RedisError::from((
+            ErrorKind::AuthenticationFailed,
+            "Password authentication failed",
+        ));
+
+        assert!(matches!(
+            Error::from(wrong_password),
+            Error::Authentication(_)
+        ));
+
+        let err = Error::from(RunError::User(RedisError::from((
+            ErrorKind::IoError,
+            "connection refused",
+        ))));
+
+        assert!(matches!(err, Error::Cluster(RunError::User(_))));
+    }
+
+    #[test]
+    fn test_expired_when() {
+        assert_eq!(1060, expired_when(1000, 60));
+        assert_eq!(u64::MAX, expired_when(1000, 0));
+    }
+
+    #[test]
+    fn test_deserialize_hosts() {
+        let expected = vec![
+            "redis://localhost:6379".to_string(),
+            "redis://localhost:6380".to_string(),
+        ];
+
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts_csv": "redis://localhost:6379, redis://localhost:6380",
+            "expire_seconds": 1,
+            "max_connections": 2,
+        }))
+        .unwrap();
+
+        assert_eq!(expected, config.hosts);
+
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379", "redis://localhost:6380"],
+            "expire_seconds": 1,
+            "max_connections": 2,
+        }))
+        .unwrap();
+
+        assert_eq!(expected, config.hosts);
+    }
+
+    #[test]
+    fn test_deserialize_defaults() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+        }))
+        .unwrap();
+
+        assert_eq!(3600, config.expire_seconds);
+        assert_eq!(10, config.max_connections);
+        assert_eq!(30, config.connection_timeout_seconds);
+    }
+
+    #[test]
+    #[cfg(feature = "config-schema")]
+    fn test_schema() {
+        let schema = crate::config::schema::<RedisConfig>();
+
+        assert_eq!(
+            json!([{"type": "array", "items": {"type": "string"}}, {"type": "string"}]),
+            schema["properties"]["hosts"]["anyOf"]
+        );
+        assert_eq!(json!(["hosts"]), schema["required"]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let expected: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379", "redis://localhost:6380"],
+            "username": "default",
+            "password": "supersecuretoken",
+        }))
+        .unwrap();
+
+        let actual = RedisConfig::builder()
+            .hosts(&["redis://localhost:6379", "redis://localhost:6380"])
+            .username("default")
+            .password("supersecuretoken")
+            .build()
+            .unwrap();
+
+        assert_eq!(expected, actual);
+
+        let err = RedisConfig::builder().build().err().unwrap();
+
+        assert_eq!(
+            "config error: redis hosts must not be empty",
+            err.to_string()
+        );
+
+        let err = RedisConfig::builder()
+            .hosts(&["redis://localhost:6379", " "])
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            "config error: redis host at index 1 must not be blank",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_one_empty_hosts() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": [],
+        }))
+        .unwrap();
+
+        let err = config.connect_one().await.err().unwrap();
+
+        assert_eq!(
+            "config error: redis hosts must not be empty",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_connection_infos_with_ports() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": "redis://h0:6380,h1:7000,h2,127.0.0.1:7001",
+        }))
+        .unwrap();
+
+        let addrs = config
+            .connection_infos()
+            .unwrap()
+            .into_iter()
+            .map(|info| info.addr)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                redis_rs::ConnectionAddr::Tcp("h0".to_string(), 6380),
+                redis_rs::ConnectionAddr::Tcp("h1".to_string(), 7000),
+                redis_rs::ConnectionAddr::Tcp("h2".to_string(), 6379),
+                redis_rs::ConnectionAddr::Tcp("127.0.0.1".to_string(), 7001),
+            ],
+            addrs
+        );
+    }
+
+    #[test]
+    fn test_connection_infos() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+        }))
+        .unwrap();
+
+        let infos = config.connection_infos().unwrap();
+
+        assert_eq!(
+            redis_rs::ConnectionAddr::Tcp("localhost".to_string(), 6379),
+            infos[0].addr
+        );
+        assert_eq!(None, infos[0].redis.username);
+        assert_eq!(None, infos[0].redis.password);
+
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+            "username": "default",
+            "password": "supersecuretoken",
+        }))
+        .unwrap();
+
+        let infos = config.connection_infos().unwrap();
+
+        assert_eq!(Some("default".to_string()), infos[0].redis.username);
+        assert_eq!(
+            Some("supersecuretoken".to_string()),
+            infos[0].redis.password
+        );
+        assert!(!format!("{:?}", config).contains("supersecuretoken"));
+
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+            "username": "app-user",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            "config error: redis username requires password",
+            config.connection_infos().err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "redis-tls")]
+    fn test_connection_infos_tls() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+            "use_tls": true,
+        }))
+        .unwrap();
+
+        let infos = config.connection_infos().unwrap();
+
+        assert_eq!(
+            redis_rs::ConnectionAddr::TcpTls {
+                host: "localhost".to_string(),
+                port: 6379,
+                insecure: false,
+            },
+            infos[0].addr
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "redis-tls"))]
+    fn test_connection_infos_tls_without_feature() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "hosts": ["redis://localhost:6379"],
+            "use_tls": true,
+        }))
+        .unwrap();
+
+        let err = config.connection_infos().err().unwrap();
+
+        assert_eq!(
+            "config error: redis use_tls requires `redis-tls` feature",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_pool_empty_hosts() {
+        let config = RedisConfig {
+            hosts: vec![],
+            expire_seconds: 1,
+            max_connections: 2,
+            connection_timeout_seconds: 1,
+            username: None,
+            password: None,
+            use_tls: false,
+        };
+
+        let err = config.init_pool().await.err().unwrap();
+
+        assert_eq!(
+            "config error: redis hosts must not be empty",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_pool_validated_empty_hosts() {
+        let config = RedisConfig {
+            hosts: vec![],
+            expire_seconds: 1,
+            max_connections: 2,
+            connection_timeout_seconds: 1,
+            username: None,
+            password: None,
+            use_tls: false,
+        };
+
+        let err = config.init_pool_validated().await.err().unwrap();
+
+        assert_eq!(
+            "config error: redis hosts must not be empty",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_pool_timeout() {
+        // Accept connections, but never reply.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let config = RedisConfig::builder()
+            .hosts(&[address.to_string()])
+            .connection_timeout_seconds(1)
+            .build()
+            .unwrap();
+        let timeout = std::time::Duration::from_secs(3);
+
+        let started_at = std::time::Instant::now();
+        let pool = config.init_pool().await.unwrap();
+        let result = pool.get().await.map_err(Error::from);
+
+        assert!(started_at.elapsed() < timeout);
+        assert!(
+            matches!(result, Err(Error::Cluster(RunError::TimedOut))),
+            "Should time out. Got {:?}",
+            result.err()
+        );
+
+        let started_at = std::time::Instant::now();
+        let result = config.init_pool_validated().await;
+
+        assert!(started_at.elapsed() < timeout);
+        assert!(
+            matches!(result, Err(Error::Cluster(RunError::TimedOut))),
+            "Should time out. Got {:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_init_pool_on() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // Stand-in for a custom executor, without IO and time drivers.
+        let executor = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let config = RedisConfig {
+            hosts: vec![],
+            expire_seconds: 1,
+            max_connections: 2,
+            connection_timeout_seconds: 1,
+            username: None,
+            password: None,
+            use_tls: false,
+        };
+
+        let err = executor
+            .block_on(config.init_pool_on(runtime.handle()))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            "config error: redis hosts must not be empty",
+            err.to_string()
+        );
+    }
+}
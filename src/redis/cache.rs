@@ -0,0 +1,430 @@
+//! Cache-aside abstraction decoupled from Redis, so service logic could swap backends or use
+//! a mock in tests.
+
+use std::future::Future;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use redis_rs::{AsyncCommands, FromRedisValue, ToRedisArgs};
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use super::{metrics, Error, GetOrFetchExt, Result, NO_EXPIRY};
+
+/// Cache backend. Implemented for Redis cluster connection and
+/// [InMemoryCache](super::InMemoryCache).
+///
+/// Method names differ from `AsyncCommands` and [GetOrFetchExt], so all of them could be
+/// imported together.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::redis::Cache;
+/// async fn stock_price<C: Cache + Send>(cache: &mut C, symbol: &str) -> avantis_utils::redis::Result<String> {
+///     cache
+///         .get_or_load(symbol, || async { Ok("100".to_string()) }, 60)
+///         .await
+/// }
+///
+/// // together with other Redis traits.
+/// # use avantis_utils::redis::GetOrFetchExt;
+/// # use redis_rs::AsyncCommands;
+/// async fn cached_price(connection: &mut redis_cluster_async::Connection) -> avantis_utils::redis::Result<String> {
+///     let _: Option<String> = connection.get("AAPL").await?;
+///     let _: String = connection.get_or_fetch("AAPL", || async { Ok("100".to_string()) }, 60).await?;
+///
+///     stock_price(connection, "AAPL").await
+/// }
+/// ```
+#[async_trait]
+pub trait Cache {
+    /// Get cached value of `key`. Return `None` for missing or expired keys.
+    async fn get_cached<K, V>(&mut self, key: K) -> Result<Option<V>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + Send + Sync;
+
+    /// Cache `value` of `key` for `expire_seconds`. `expire_seconds` of `0` means no expiry.
+    async fn set_cached<K, V>(&mut self, key: K, value: &V, expire_seconds: usize) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: ToRedisArgs + Send + Sync;
+
+    /// Remove cached value of `key`, if any.
+    async fn invalidate<K>(&mut self, key: K) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync;
+
+    /// Get cached value of `key`, or load it with `data_loader` and cache it for `expire_seconds`.
+    /// `expire_seconds` of `0` means no expiry.
+    async fn get_or_load<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<V>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        if let Some(value) = self.get_cached(&key).await? {
+            return Ok(value);
+        }
+
+        let value = data_loader().await?;
+        self.set_cached(&key, &value, expire_seconds).await?;
+
+        Ok(value)
+    }
+
+    /// Like [Cache::get_or_load], but return `key` back with the value, ie. to tell results
+    /// apart when fetching many keys concurrently.
+    async fn get_or_load_keyed<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<(K, V)>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        let value = self.get_or_load(&key, data_loader, expire_seconds).await?;
+
+        Ok((key, value))
+    }
+
+    /// Load every entry with its loader and cache it for `expire_seconds`, ie. to populate
+    /// the cache before serving traffic. At most `max_concurrency` loaders run at a time, each
+    /// in its own task, and the next one starts as soon as any of them completes. A failed entry
+    /// is reported in [WarmReport] and doesn't abort the others. Dropping the returned future
+    /// aborts loaders still running.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::redis::Cache;
+    /// # async fn example<C: Cache + Send>(cache: &mut C) {
+    /// let entries = ["AAPL", "GOOG"]
+    ///     .into_iter()
+    ///     .map(|symbol| (symbol.to_string(), move || async move { Ok(format!("{} price", symbol)) }))
+    ///     .collect();
+    ///
+    /// let report = cache.warm(entries, 60, 8).await;
+    ///
+    /// println!("warmed {} keys, {} failed", report.succeeded, report.failed.len());
+    /// # }
+    /// ```
+    async fn warm<V, F, Fut>(
+        &mut self,
+        entries: Vec<(String, F)>,
+        expire_seconds: usize,
+        max_concurrency: usize,
+    ) -> WarmReport
+    where
+        Self: Send,
+        V: ToRedisArgs + Send + Sync + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<V>> + Send + 'static,
+    {
+        let mut report = WarmReport::default();
+        let mut entries = entries.into_iter();
+        let mut loading = JoinSet::new();
+        // keys of loaders not joined yet, to report loaders that panicked.
+        let mut pending = Vec::new();
+
+        loop {
+            while loading.len() < max_concurrency.max(1) {
+                match entries.next() {
+                    Some((key, loader)) => {
+                        pending.push(key.clone());
+                        metrics::spawn_named_in(&mut loading, async move { (key, loader().await) });
+                    }
+                    None => break,
+                }
+            }
+
+            let (key, loaded) = match loading.join_next().await {
+                Some(Ok(loaded)) => loaded,
+                Some(Err(err)) => {
+                    warn!("cache warm loader failed: {}", err);
+                    continue;
+                }
+                None => break,
+            };
+            if let Some(index) = pending.iter().position(|pending| *pending == key) {
+                pending.swap_remove(index);
+            }
+
+            let result = match loaded {
+                Ok(value) => self.set_cached(&key, &value, expire_seconds).await,
+                Err(err) => Err(Error::Data(err)),
+            };
+
+            match result {
+                Ok(()) => report.succeeded += 1,
+                Err(err) => {
+                    warn!("unable to warm cache key `{}`: {}", key, err);
+                    report.failed.push((key, err));
+                }
+            }
+        }
+
+        for key in pending {
+            warn!("unable to warm cache key `{}`: loader panicked", key);
+            report
+                .failed
+                .push((key, Error::Data(anyhow!("loader failed: panicked"))));
+        }
+
+        report
+    }
+}
+
+/// Result of [Cache::warm].
+#[derive(Debug, Default)]
+pub struct WarmReport {
+    pub succeeded: usize,
+    /// Keys that failed to load or to be cached, with their errors.
+    pub failed: Vec<(String, Error)>,
+}
+
+impl WarmReport {
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Redis implementation. [Cache::get_or_load] is [GetOrFetchExt::get_or_fetch].
+#[async_trait]
+impl Cache for redis_cluster_async::Connection {
+    async fn get_cached<K, V>(&mut self, key: K) -> Result<Option<V>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + Send + Sync,
+    {
+        Ok(self.get(key).await?)
+    }
+
+    async fn set_cached<K, V>(&mut self, key: K, value: &V, expire_seconds: usize) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: ToRedisArgs + Send + Sync,
+    {
+        match expire_seconds {
+            NO_EXPIRY => Ok(self.set(key, value).await?),
+            _ => Ok(self.set_ex(key, value, expire_seconds).await?),
+        }
+    }
+
+    async fn invalidate<K>(&mut self, key: K) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+    {
+        Ok(self.del(key).await?)
+    }
+
+    async fn get_or_load<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<V>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        self.get_or_fetch(key, data_loader, expire_seconds).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use redis_rs::Value;
+
+    use super::*;
+
+    /// Minimal cache relying on default [Cache::get_or_load], like a mock in service tests.
+    #[derive(Default)]
+    struct MockCache(HashMap<Vec<u8>, Vec<u8>>);
+
+    #[async_trait]
+    impl Cache for MockCache {
+        async fn get_cached<K, V>(&mut self, key: K) -> Result<Option<V>>
+        where
+            K: ToRedisArgs + Send + Sync,
+            V: FromRedisValue + Send + Sync,
+        {
+            self.0
+                .get(&key.to_redis_args().concat())
+                .map(|value| Ok(V::from_redis_value(&Value::Data(value.clone()))?))
+                .transpose()
+        }
+
+        async fn set_cached<K, V>(&mut self, key: K, value: &V, _: usize) -> Result<()>
+        where
+            K: ToRedisArgs + Send + Sync,
+            V: ToRedisArgs + Send + Sync,
+        {
+            self.0
+                .insert(key.to_redis_args().concat(), value.to_redis_args().concat());
+            Ok(())
+        }
+
+        async fn invalidate<K>(&mut self, key: K) -> Result<()>
+        where
+            K: ToRedisArgs + Send + Sync,
+        {
+            self.0.remove(&key.to_redis_args().concat());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_get_or_load() {
+        let mut cache = MockCache::default();
+
+        let result: String = cache
+            .get_or_load("key", || async { Ok("first".to_string()) }, 60)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        let result: String = cache
+            .get_or_load("key", || async { Ok("second".to_string()) }, 60)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        cache.invalidate("key").await.unwrap();
+
+        let result: Option<String> = cache.get_cached("key").await.unwrap();
+        assert_eq!(None, result);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_load_keyed() {
+        let mut cache = MockCache::default();
+
+        let mut results = vec![];
+        for key in ["key1".to_string(), "key2".to_string()] {
+            let loaded = format!("{} value", key);
+            let result: (String, String) = cache
+                .get_or_load_keyed(key, || async { Ok(loaded) }, 60)
+                .await
+                .unwrap();
+            results.push(result);
+        }
+
+        assert_eq!(
+            vec![
+                ("key1".to_string(), "key1 value".to_string()),
+                ("key2".to_string(), "key2 value".to_string()),
+            ],
+            results
+        );
+
+        let result: Option<String> = cache.get_cached("key2").await.unwrap();
+        assert_eq!(Some("key2 value".to_string()), result);
+    }
+
+    #[tokio::test]
+    async fn test_warm() {
+        let mut cache = MockCache::default();
+
+        let entries = (0..5)
+            .map(|n| {
+                (format!("key{}", n), move || async move {
+                    match n {
+                        3 => Err(anyhow!("unable to load {}", n)),
+                        _ => Ok(n.to_string()),
+                    }
+                })
+            })
+            .collect();
+
+        let report = cache.warm(entries, 60, 2).await;
+
+        assert!(!report.is_ok());
+        assert_eq!(4, report.succeeded);
+        assert_eq!(1, report.failed.len());
+        assert_eq!("key3", report.failed[0].0);
+
+        let result: Option<String> = cache.get_cached("key4").await.unwrap();
+        assert_eq!(Some("4".to_string()), result);
+        let result: Option<String> = cache.get_cached("key3").await.unwrap();
+        assert_eq!(None, result);
+    }
+
+    #[tokio::test]
+    async fn test_warm_refills_as_loaders_complete() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let mut cache = MockCache::default();
+        let key2_loaded = Arc::new(AtomicBool::new(false));
+
+        // key0 waits for key2, which only starts once key1 completes.
+        let entries = (0..3)
+            .map(|n| {
+                let key2_loaded = key2_loaded.clone();
+                (format!("key{}", n), move || async move {
+                    match n {
+                        0 => {
+                            while !key2_loaded.load(Ordering::SeqCst) {
+                                tokio::time::sleep(Duration::from_millis(1)).await;
+                            }
+                        }
+                        2 => key2_loaded.store(true, Ordering::SeqCst),
+                        _ => {}
+                    }
+                    Ok(n.to_string())
+                })
+            })
+            .collect();
+
+        let report = tokio::time::timeout(Duration::from_secs(5), cache.warm(entries, 60, 2))
+            .await
+            .unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(3, report.succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_warm_aborts_loaders_on_drop() {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let mut cache = MockCache::default();
+        let loader_state = Arc::new(());
+
+        let entries = vec![("key0".to_string(), {
+            let loader_state = loader_state.clone();
+            move || async move {
+                let _loader_state = loader_state;
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok("0".to_string())
+            }
+        })];
+
+        let warm = tokio::time::timeout(Duration::from_millis(10), cache.warm(entries, 60, 1));
+        assert!(warm.await.is_err());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(1, Arc::strong_count(&loader_state));
+    }
+}
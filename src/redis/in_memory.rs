@@ -0,0 +1,376 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use redis_rs::{FromRedisValue, ToRedisArgs, Value};
+use tracing::error;
+
+use super::metrics::{self, CacheEvent};
+use super::{expired_when, GetOrFetchExt, GetOrRefreshExt, RefreshHandle, Result};
+
+/// In-memory cache backend with the same semantics as the Redis helpers.
+/// Useful for local development and tests where Redis is not available.
+///
+/// Cloning is cheap, all clones share the same entries.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::redis::GetOrFetchExt;
+/// # use avantis_utils::redis::InMemoryCache;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut cache = InMemoryCache::new();
+///
+/// let value: String = cache
+///     .get_or_fetch("key", || async { Ok("value".to_string()) }, 60)
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryCache {
+    entries: Arc<DashMap<Vec<u8>, Entry>>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    value: Value,
+    expired_when: u64,
+}
+
+impl InMemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        self.entries.clear()
+    }
+
+    fn get_entry(&self, key: &[u8]) -> Option<Entry> {
+        self.entries.get(key).map(|entry| entry.clone())
+    }
+
+    fn set_entry<V: ToRedisArgs>(&self, key: Vec<u8>, value: &V, expire_seconds: usize) {
+        self.entries.insert(
+            key,
+            Entry {
+                value: to_redis_value(value),
+                expired_when: expired_when(now(), expire_seconds),
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl super::Cache for InMemoryCache {
+    async fn get_cached<K, V>(&mut self, key: K) -> Result<Option<V>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + Send + Sync,
+    {
+        match self.get_entry(&to_key(&key)) {
+            Some(entry) if now() <= entry.expired_when => {
+                Ok(Some(V::from_redis_value(&entry.value)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn set_cached<K, V>(&mut self, key: K, value: &V, expire_seconds: usize) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: ToRedisArgs + Send + Sync,
+    {
+        self.set_entry(to_key(&key), value, expire_seconds);
+        Ok(())
+    }
+
+    async fn invalidate<K>(&mut self, key: K) -> Result<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+    {
+        self.entries.remove(&to_key(&key));
+        Ok(())
+    }
+
+    async fn get_or_load<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<V>
+    where
+        Self: Send,
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        self.get_or_fetch(key, data_loader, expire_seconds).await
+    }
+}
+
+#[async_trait]
+impl GetOrFetchExt for InMemoryCache {
+    async fn get_or_fetch<K, V, F, Fut>(
+        &mut self,
+        key: K,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<V>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: FromRedisValue + ToRedisArgs + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        let key = to_key(&key);
+
+        match self.get_entry(&key) {
+            Some(entry) if now() <= entry.expired_when => {
+                metrics::record(CacheEvent::Hit);
+                Ok(V::from_redis_value(&entry.value)?)
+            }
+            _ => {
+                metrics::record(CacheEvent::Miss);
+                let result = data_loader().await?;
+                self.set_entry(key, &result, expire_seconds);
+                Ok(result)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl GetOrRefreshExt for InMemoryCache {
+    async fn get_or_refresh_with_handle<'a, V, F, Fut>(
+        self,
+        key: &str,
+        data_loader: F,
+        expire_seconds: usize,
+    ) -> Result<(V, Option<RefreshHandle>)>
+    where
+        V: FromRedisValue + ToRedisArgs + Send + Sync + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<V>> + Send,
+    {
+        let owned_key = key.as_bytes().to_vec();
+
+        match self.get_entry(&owned_key) {
+            Some(entry) if now() <= entry.expired_when => {
+                metrics::record(CacheEvent::Hit);
+                Ok((V::from_redis_value(&entry.value)?, None))
+            }
+            Some(entry) => {
+                metrics::record(CacheEvent::BackgroundRefresh);
+                let value = V::from_redis_value(&entry.value)?;
+
+                let handle = metrics::spawn_named(async move {
+                    match data_loader().await {
+                        Ok(new_value) => {
+                            self.set_entry(owned_key, &new_value, expire_seconds);
+                            Ok(())
+                        }
+                        Err(e) => {
+                            error!("Failed to load and set in background: {}", e);
+                            Err(e.into())
+                        }
+                    }
+                });
+
+                Ok((value, Some(handle)))
+            }
+            None => {
+                metrics::record(CacheEvent::Miss);
+                let new_value = data_loader().await?;
+                self.set_entry(owned_key, &new_value, expire_seconds);
+                Ok((new_value, None))
+            }
+        }
+    }
+}
+
+fn to_key<K: ToRedisArgs>(key: &K) -> Vec<u8> {
+    key.to_redis_args().concat()
+}
+
+fn to_redis_value<V: ToRedisArgs>(value: &V) -> Value {
+    let mut args = value.to_redis_args();
+
+    if args.len() == 1 {
+        Value::Data(args.remove(0))
+    } else {
+        Value::Bulk(args.into_iter().map(Value::Data).collect())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::super::Error;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_or_fetch() {
+        let mut cache = InMemoryCache::new();
+
+        let result: String = cache
+            .get_or_fetch("key", || async { Ok("first".to_string()) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        let result: String = cache
+            .get_or_fetch("key", || async { Ok("second".to_string()) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        let result: Result<String> = cache
+            .get_or_fetch(
+                "other_key",
+                || async { Err(anyhow::anyhow!("unable to load data")) },
+                1000,
+            )
+            .await;
+        assert!(
+            matches!(result, Err(Error::Data(ref err)) if err.to_string() == "unable to load data")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_or_fetch_vec() {
+        let mut cache = InMemoryCache::new();
+
+        let result: Vec<String> = cache
+            .get_or_fetch_vec("key", || async { Ok(vec!["a".to_string()]) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!(vec!["a".to_string()], result);
+
+        let result: Vec<String> = cache
+            .get_or_fetch_vec("key", || async { Ok(vec!["b".to_string()]) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!(vec!["a".to_string()], result);
+    }
+
+    #[tokio::test]
+    async fn test_no_expiry() {
+        let mut cache = InMemoryCache::new();
+
+        let _: String = cache
+            .get_or_fetch("key", || async { Ok("first".to_string()) }, 0)
+            .await
+            .unwrap();
+        let _: String = cache
+            .clone()
+            .get_or_refresh("other_key", || async { Ok("first".to_string()) }, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(u64::MAX, cache.get_entry(b"key").unwrap().expired_when);
+        assert_eq!(
+            u64::MAX,
+            cache.get_entry(b"other_key").unwrap().expired_when
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_or_refresh() {
+        let cache = InMemoryCache::new();
+
+        let result: String = cache
+            .clone()
+            .get_or_refresh("key", || async { Ok("first".to_string()) }, 1)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+
+        let (result, handle): (String, _) = cache
+            .clone()
+            .get_or_refresh_with_handle("key", || async { Ok("second".to_string()) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!("first", result, "Should return expired cached data");
+
+        handle
+            .expect("Should refresh in background")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let result: String = cache
+            .clone()
+            .get_or_refresh("key", || async { Ok("third".to_string()) }, 1000)
+            .await
+            .unwrap();
+        assert_eq!("second", result, "Should return refreshed data");
+    }
+
+    #[tokio::test]
+    async fn test_get_or_refresh_with_handle_error() {
+        let cache = InMemoryCache::new();
+
+        let (_, handle): (String, _) = cache
+            .clone()
+            .get_or_refresh_with_handle("key", || async { Ok("first".to_string()) }, 1)
+            .await
+            .unwrap();
+        assert!(handle.is_none(), "Should not refresh on miss");
+
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+
+        let (result, handle): (String, _) = cache
+            .clone()
+            .get_or_refresh_with_handle("key", || async { Err(anyhow::anyhow!("boom")) }, 1)
+            .await
+            .unwrap();
+        assert_eq!("first", result);
+
+        let err = handle.unwrap().await.unwrap().err().unwrap();
+        assert!(matches!(err, Error::Data(_)));
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        use super::super::Cache;
+
+        async fn cached_name<C: Cache + Send>(cache: &mut C) -> Result<String> {
+            cache
+                .get_or_load("name", || async { Ok("first".to_string()) }, 1000)
+                .await
+        }
+
+        let mut cache = InMemoryCache::new();
+
+        assert_eq!("first", cached_name(&mut cache).await.unwrap());
+
+        cache.set_cached("name", &"second", 1000).await.unwrap();
+        assert_eq!("second", cached_name(&mut cache).await.unwrap());
+
+        cache.invalidate("name").await.unwrap();
+        let result: Option<String> = cache.get_cached("name").await.unwrap();
+        assert_eq!(None, result);
+
+        cache.set_cached("name", &"expired", 1).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        let result: Option<String> = cache.get_cached("name").await.unwrap();
+        assert_eq!(None, result);
+    }
+}
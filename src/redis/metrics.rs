@@ -0,0 +1,170 @@
+//! Cache hit rate instrumentation for [GetOrFetchExt](super::GetOrFetchExt) and
+//! [GetOrRefreshExt](super::GetOrRefreshExt).
+//!
+//! With `redis-metrics` feature, events are recorded as OpenTelemetry counters from the global
+//! meter, tagged by `cache` name. Otherwise, events are emitted as `trace` level tracing events.
+
+use std::future::Future;
+
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+
+tokio::task_local! {
+    static CACHE_NAME: &'static str;
+}
+
+const DEFAULT_CACHE_NAME: &str = "default";
+
+/// Tag cache events recorded while running `future` with `name`.
+/// Events outside of this scope are tagged as `default`.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::redis::with_cache_name;
+/// # use avantis_utils::redis::GetOrFetchExt;
+/// # async fn example(mut connection: avantis_utils::redis::Connection) -> avantis_utils::redis::Result<()> {
+/// let price: String = with_cache_name(
+///     "stock_price",
+///     connection.get_or_fetch("AAPL", || async { Ok("100".to_string()) }, 60),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn with_cache_name<F: Future>(name: &'static str, future: F) -> F::Output {
+    CACHE_NAME.scope(name, future).await
+}
+
+fn cache_name() -> &'static str {
+    CACHE_NAME
+        .try_with(|name| *name)
+        .unwrap_or(DEFAULT_CACHE_NAME)
+}
+
+/// Spawn `future` with the cache name of the caller, as task-locals are not inherited.
+pub(crate) fn spawn_named<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(with_cache_name(cache_name(), future))
+}
+
+/// Like [spawn_named], spawn `future` into `tasks`, so it is aborted when `tasks` is dropped.
+pub(crate) fn spawn_named_in<F>(tasks: &mut JoinSet<F::Output>, future: F) -> AbortHandle
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tasks.spawn(with_cache_name(cache_name(), future))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CacheEvent {
+    Hit,
+    Miss,
+    Error,
+    BackgroundRefresh,
+}
+
+impl CacheEvent {
+    #[cfg_attr(feature = "redis-metrics", allow(dead_code))]
+    fn as_str(&self) -> &'static str {
+        match self {
+            CacheEvent::Hit => "cache_hit",
+            CacheEvent::Miss => "cache_miss",
+            CacheEvent::Error => "cache_error",
+            CacheEvent::BackgroundRefresh => "background_refresh",
+        }
+    }
+}
+
+#[cfg(feature = "redis-metrics")]
+pub(crate) fn record(event: CacheEvent) {
+    use std::sync::OnceLock;
+
+    use opentelemetry::global;
+    use opentelemetry::metrics::Counter;
+    use opentelemetry::KeyValue;
+
+    struct CacheMetrics {
+        hit: Counter<u64>,
+        miss: Counter<u64>,
+        error: Counter<u64>,
+        background_refresh: Counter<u64>,
+    }
+
+    static METRICS: OnceLock<CacheMetrics> = OnceLock::new();
+
+    let metrics = METRICS.get_or_init(|| {
+        let meter = global::meter("avantis-utils");
+
+        CacheMetrics {
+            hit: meter
+                .u64_counter("cache.hit")
+                .with_description("Number of cache hits")
+                .init(),
+            miss: meter
+                .u64_counter("cache.miss")
+                .with_description("Number of cache misses")
+                .init(),
+            error: meter
+                .u64_counter("cache.error")
+                .with_description("Number of cache errors, falling back to data loader")
+                .init(),
+            background_refresh: meter
+                .u64_counter("cache.background_refresh")
+                .with_description("Number of stale values served while refreshing in background")
+                .init(),
+        }
+    });
+
+    let counter = match event {
+        CacheEvent::Hit => &metrics.hit,
+        CacheEvent::Miss => &metrics.miss,
+        CacheEvent::Error => &metrics.error,
+        CacheEvent::BackgroundRefresh => &metrics.background_refresh,
+    };
+
+    counter.add(1, &[KeyValue::new("cache", cache_name())]);
+}
+
+#[cfg(not(feature = "redis-metrics"))]
+pub(crate) fn record(event: CacheEvent) {
+    tracing::trace!(cache = cache_name(), event = event.as_str(), "cache event");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_cache_name() {
+        assert_eq!("default", cache_name());
+
+        let name = with_cache_name("stock_price", async { cache_name() }).await;
+
+        assert_eq!("stock_price", name);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_named() {
+        let name = with_cache_name("stock_price", async {
+            spawn_named(async { cache_name() }).await.unwrap()
+        })
+        .await;
+
+        assert_eq!("stock_price", name);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_named_in() {
+        let mut tasks = JoinSet::new();
+        with_cache_name("stock_price", async {
+            spawn_named_in(&mut tasks, async { cache_name() });
+        })
+        .await;
+
+        assert_eq!("stock_price", tasks.join_next().await.unwrap().unwrap());
+    }
+}
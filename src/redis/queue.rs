@@ -0,0 +1,252 @@
+//! Lightweight work queues over Redis lists.
+//!
+//! Items are pushed with `LPUSH` and popped with `BRPOP`, in FIFO order, serialized as JSON.
+//! For at-least-once processing, [RedisQueue::dequeue_reliable] moves items to a processing
+//! list with `BRPOPLPUSH` until they are acked, so items are not lost if a consumer crashes.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use redis_rs::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{Pool, Result};
+
+/// Work queues over Redis lists of [Pool].
+///
+/// Blocking pops hold a pooled connection until an item is available or timeout is reached,
+/// prefer a dedicated pool for consumers so they don't starve cache lookups.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use avantis_utils::redis::RedisQueue;
+/// # async fn example(pool: avantis_utils::redis::Pool) -> avantis_utils::redis::Result<()> {
+/// let queue = RedisQueue::new(pool);
+///
+/// queue.enqueue("emails", &"hello@example.com").await?;
+///
+/// if let Some(reserved) = queue
+///     .dequeue_reliable::<String>("emails", Duration::from_secs(5))
+///     .await?
+/// {
+///     println!("sending to {}", reserved.item);
+///     queue.ack("emails", &reserved).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisQueue {
+    pool: Pool,
+}
+
+/// Item dequeued by [RedisQueue::dequeue_reliable]. It stays in the processing list of its
+/// queue until [RedisQueue::ack] is called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reserved<T> {
+    pub item: T,
+    payload: String,
+}
+
+impl RedisQueue {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+
+    /// Push `item` to `queue`.
+    pub async fn enqueue<T: Serialize>(&self, queue: &str, item: &T) -> Result<()> {
+        let payload = serde_json::to_string(item).map_err(|err| {
+            anyhow!(
+                "Unable to encode item of queue {} from type {}: {}",
+                queue,
+                std::any::type_name::<T>(),
+                err
+            )
+        })?;
+
+        let mut connection = self.pool.get().await?;
+        let _: () = connection.lpush(queue, payload).await?;
+
+        Ok(())
+    }
+
+    /// Pop the oldest item of `queue`, waiting up to `timeout` for one to be pushed.
+    /// Return `None` on timeout. Zero `timeout` doesn't wait.
+    pub async fn dequeue<T: DeserializeOwned>(
+        &self,
+        queue: &str,
+        timeout: Duration,
+    ) -> Result<Option<T>> {
+        let mut connection = self.pool.get().await?;
+
+        let payload: Option<String> = match blocking_timeout_seconds(timeout) {
+            Some(seconds) => {
+                let popped: Option<(String, String)> = redis_rs::cmd("BRPOP")
+                    .arg(queue)
+                    .arg(seconds)
+                    .query_async(&mut *connection)
+                    .await?;
+                popped.map(|(_, payload)| payload)
+            }
+            None => {
+                redis_rs::cmd("RPOP")
+                    .arg(queue)
+                    .query_async(&mut *connection)
+                    .await?
+            }
+        };
+
+        payload.map(|payload| decode(queue, &payload)).transpose()
+    }
+
+    /// Like [RedisQueue::dequeue], but atomically move the item to the processing list of
+    /// `queue` until it is acked with [RedisQueue::ack]. Items of crashed consumers can be
+    /// put back with [RedisQueue::recover].
+    ///
+    /// An item that can't be decoded is left in the processing list.
+    pub async fn dequeue_reliable<T: DeserializeOwned>(
+        &self,
+        queue: &str,
+        timeout: Duration,
+    ) -> Result<Option<Reserved<T>>> {
+        let processing = Self::processing_list(queue);
+        let mut connection = self.pool.get().await?;
+
+        let payload: Option<String> = match blocking_timeout_seconds(timeout) {
+            Some(seconds) => {
+                redis_rs::cmd("BRPOPLPUSH")
+                    .arg(queue)
+                    .arg(&processing)
+                    .arg(seconds)
+                    .query_async(&mut *connection)
+                    .await?
+            }
+            None => {
+                redis_rs::cmd("RPOPLPUSH")
+                    .arg(queue)
+                    .arg(&processing)
+                    .query_async(&mut *connection)
+                    .await?
+            }
+        };
+
+        payload
+            .map(|payload| {
+                Ok(Reserved {
+                    item: decode(queue, &payload)?,
+                    payload,
+                })
+            })
+            .transpose()
+    }
+
+    /// Remove `reserved` from the processing list of `queue` once it is done.
+    pub async fn ack<T>(&self, queue: &str, reserved: &Reserved<T>) -> Result<()> {
+        let mut connection = self.pool.get().await?;
+        let _: () = connection
+            .lrem(Self::processing_list(queue), 1, &reserved.payload)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Move all items of the processing list of `queue` back to `queue`. Return the number of
+    /// items moved. Only call it when no consumer is running, ie. on startup, otherwise
+    /// in-flight items will be processed twice.
+    pub async fn recover(&self, queue: &str) -> Result<usize> {
+        let processing = Self::processing_list(queue);
+        let mut connection = self.pool.get().await?;
+        let mut count = 0;
+
+        loop {
+            let moved: Option<String> = connection.rpoplpush(processing.as_str(), queue).await?;
+            match moved {
+                Some(_) => count += 1,
+                None => return Ok(count),
+            }
+        }
+    }
+
+    /// Name of the processing list of `queue`. It shares the hash slot of `queue`, so
+    /// `RPOPLPUSH` works on cluster.
+    pub fn processing_list(queue: &str) -> String {
+        if has_hash_tag(queue) {
+            format!("{}:processing", queue)
+        } else {
+            format!("{{{}}}:processing", queue)
+        }
+    }
+}
+
+fn decode<T: DeserializeOwned>(queue: &str, payload: &str) -> Result<T> {
+    serde_json::from_str(payload).map_err(|err| {
+        anyhow!(
+            "Unable to decode item of queue {} into type {}: {}",
+            queue,
+            std::any::type_name::<T>(),
+            err
+        )
+        .into()
+    })
+}
+
+/// Timeout of blocking pops in seconds, or `None` for zero `timeout`, which Redis treats as
+/// waiting forever. Round up to milliseconds since Redis truncates the rest.
+fn blocking_timeout_seconds(timeout: Duration) -> Option<f64> {
+    let millis = timeout.as_millis() as u64;
+    let millis = match Duration::from_millis(millis) < timeout {
+        true => millis + 1,
+        false => millis,
+    };
+
+    match millis {
+        0 => None,
+        millis => Some(millis as f64 / 1000.0),
+    }
+}
+
+/// Whether `key` has a hash tag, ie. a non-empty `{...}`, that decides its cluster hash slot.
+fn has_hash_tag(key: &str) -> bool {
+    key.find('{')
+        .and_then(|start| key[start + 1..].find('}'))
+        .is_some_and(|length| length > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_timeout_seconds() {
+        assert_eq!(None, blocking_timeout_seconds(Duration::ZERO));
+        assert_eq!(
+            Some(0.001),
+            blocking_timeout_seconds(Duration::from_micros(1))
+        );
+        assert_eq!(
+            Some(0.25),
+            blocking_timeout_seconds(Duration::from_millis(250))
+        );
+        assert_eq!(Some(5.0), blocking_timeout_seconds(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_processing_list() {
+        assert_eq!("{jobs}:processing", RedisQueue::processing_list("jobs"));
+        assert_eq!(
+            "{app}:jobs:processing",
+            RedisQueue::processing_list("{app}:jobs")
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(1, decode::<i32>("jobs", "1").unwrap());
+
+        let err = decode::<i32>("jobs", "not json").err().unwrap();
+
+        assert!(matches!(err, super::super::Error::Data(_)));
+    }
+}
@@ -1,28 +1,122 @@
 use gethostname::gethostname;
 use opentelemetry::global::set_text_map_propagator;
+use opentelemetry::global::set_tracer_provider;
+use opentelemetry::sdk::export::trace::stdout;
 use opentelemetry::sdk::propagation::TraceContextPropagator;
 use opentelemetry::sdk::trace;
+use opentelemetry::sdk::trace::Sampler;
 use opentelemetry::sdk::Resource;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::trace::TracerProvider;
 use opentelemetry::KeyValue;
+use opentelemetry_otlp::SpanExporterBuilder;
 use opentelemetry_otlp::WithExportConfig;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::future::Future;
+use std::time::Duration;
 use thiserror::Error;
+use tracing::error;
 use tracing::info;
+use tracing::instrument::WithSubscriber;
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::filter::FilterFn;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::Layer;
 use tracing_subscriber::{EnvFilter, Registry};
 
-#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+use self::exporter::CircuitBreakerExporter;
+
+#[cfg(feature = "config")]
+use crate::config::Environment;
+
+pub mod correlation;
+pub mod exporter;
+#[cfg(feature = "telemetry-grpc")]
+pub mod grpc;
+#[cfg(feature = "telemetry-http")]
+pub mod http;
+#[cfg(feature = "telemetry-metrics")]
+pub mod metrics;
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "config-schema", derive(schemars::JsonSchema))]
 pub struct TelemetrySetting {
     pub otel_collector_endpoint: String,
+    /// Targets to silence. An entry also silences its submodules, ie. `h2` silences `h2::client`.
     pub disabled_targets: HashSet<String>,
+    /// Base log level. Default based on environment if empty or missing, see
+    /// [TelemetrySetting::log_level_for].
+    #[serde(default)]
     pub log_level: String,
+    /// Per-target log levels, ie. `sqlx: warn`. Combined with `log_level` as base level.
+    /// Env `RUST_LOG` still takes precedence if set.
+    #[serde(default)]
+    pub targets: HashMap<String, String>,
+    /// Also print spans to stdout, in addition to exporting them to the collector.
+    /// Useful to check if spans are produced without a running collector.
+    #[serde(default)]
+    pub otel_debug: bool,
+    /// `host.name` resource attribute. Default to env `POD_NAME`, then env `HOSTNAME`,
+    /// then hostname of the machine.
+    #[serde(default)]
+    pub host_name: Option<String>,
+    /// `service.version` resource attribute. Default to env `CARGO_PKG_VERSION` if set.
+    #[serde(default)]
+    pub service_version: Option<String>,
+    /// `deployment.environment` resource attribute. Default to env `APP_ENVIRONMENT`,
+    /// the same env used by `config::Environment::from_env`.
+    #[serde(default)]
+    pub deployment_environment: Option<String>,
+    /// Additional resource attributes. Override attributes above on conflict.
+    #[serde(default)]
+    pub resource_attributes: HashMap<String, String>,
+    /// Install [install_panic_hook] on [TelemetrySetting::init_telemetry]. Default to `true`.
+    #[serde(default = "default_panic_hook")]
+    pub panic_hook: bool,
+    /// Ratio of traces to sample, from `0.0` to `1.0`. Child spans follow the decision of their
+    /// parent. Default based on environment, see [TelemetrySetting::sampling_ratio_for].
+    #[serde(default)]
+    pub sampling_ratio: Option<f64>,
+    /// Consecutive failed exports to the collector before skipping exports for
+    /// `export_cooldown_seconds`. See [CircuitBreakerExporter]. Default to `3`.
+    #[serde(default = "default_export_failure_threshold")]
+    pub export_failure_threshold: u32,
+    /// Default to `60`.
+    #[serde(default = "default_export_cooldown_seconds")]
+    pub export_cooldown_seconds: u64,
+    /// Include `file` and `line` of the log origin in logs. Default to `false` to keep logs small.
+    #[serde(default)]
+    pub include_location: bool,
+}
+
+/// Default sampling ratio of [Environment::Production] when `sampling_ratio` is unset.
+pub const PRODUCTION_SAMPLING_RATIO: f64 = 0.1;
+
+/// Default log level in production. See [TelemetrySetting::log_level_for].
+pub const PRODUCTION_LOG_LEVEL: &str = "info";
+
+/// Default log level outside production. See [TelemetrySetting::log_level_for].
+pub const DEVELOPMENT_LOG_LEVEL: &str = "debug";
+
+const LOCATION_FIELDS: [&str; 2] = ["file", "line"];
+
+fn default_panic_hook() -> bool {
+    true
+}
+
+fn default_export_failure_threshold() -> u32 {
+    3
+}
+
+fn default_export_cooldown_seconds() -> u64 {
+    60
 }
 
 impl TelemetrySetting {
@@ -30,14 +124,48 @@ impl TelemetrySetting {
     where
         S: Subscriber,
     {
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(self.log_level.clone()))
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(self.log_directives()))
+    }
+
+    /// `EnvFilter` directives from `log_level` and `targets`, ie. `info,sqlx=warn`.
+    fn log_directives(&self) -> String {
+        let mut targets = self.targets.iter().collect::<Vec<_>>();
+        targets.sort();
+
+        std::iter::once(self.log_level())
+            .chain(
+                targets
+                    .into_iter()
+                    .map(|(target, level)| format!("{}={}", target, level)),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
     }
 
     fn bunyan_formatter<S>(&self, service_name: &'static str) -> impl Layer<S>
     where
         S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     {
-        BunyanFormattingLayer::new(service_name.to_string(), std::io::stdout)
+        self.bunyan_formatter_with_writer(service_name, std::io::stdout)
+    }
+
+    fn bunyan_formatter_with_writer<S, W>(
+        &self,
+        service_name: &'static str,
+        make_writer: W,
+    ) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+        W: for<'writer> MakeWriter<'writer> + 'static,
+    {
+        let layer = BunyanFormattingLayer::new(service_name.to_string(), make_writer);
+
+        match self.include_location {
+            true => layer,
+            false => layer
+                .skip_fields(LOCATION_FIELDS.into_iter())
+                .expect("location fields are optional bunyan fields"),
+        }
     }
 
     fn disable_targets_filter<S>(&self) -> impl Layer<S>
@@ -45,56 +173,647 @@ impl TelemetrySetting {
         S: Subscriber,
     {
         let disabled_targets = self.disabled_targets.clone();
-        FilterFn::new(move |metadata| !disabled_targets.contains(metadata.target()))
+        FilterFn::new(move |metadata| !is_target_disabled(&disabled_targets, metadata.target()))
     }
 
-    fn tracer<S>(&self, service_name: &'static str) -> impl Layer<S>
-    where
-        S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
-    {
-        let tracer = opentelemetry_otlp::new_pipeline()
-            .tracing()
-            .with_trace_config(trace::config().with_resource(Resource::new(vec![
-                KeyValue::new("service.name", service_name),
-                KeyValue::new("host.name", gethostname().into_string().unwrap()),
-            ])))
-            .with_exporter(
-                opentelemetry_otlp::new_exporter()
-                    .tonic()
-                    .with_endpoint(self.otel_collector_endpoint.clone()),
+    fn host_name(&self) -> String {
+        self.host_name
+            .clone()
+            .or_else(|| std::env::var("POD_NAME").ok())
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .filter(|host_name| !host_name.is_empty())
+            .unwrap_or_else(|| gethostname().to_string_lossy().into_owned())
+    }
+
+    fn resource(&self, service_name: &'static str) -> Resource {
+        let service_version = self
+            .service_version
+            .clone()
+            .or_else(|| std::env::var("CARGO_PKG_VERSION").ok());
+        let deployment_environment = self
+            .deployment_environment
+            .clone()
+            .or_else(|| std::env::var("APP_ENVIRONMENT").ok());
+
+        let mut attributes = vec![
+            KeyValue::new("service.name", service_name),
+            KeyValue::new("host.name", self.host_name()),
+        ];
+        attributes.extend(service_version.map(|version| KeyValue::new("service.version", version)));
+        attributes.extend(
+            deployment_environment
+                .map(|environment| KeyValue::new("deployment.environment", environment)),
+        );
+        attributes.extend(
+            self.resource_attributes
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+        );
+
+        Resource::new(attributes)
+    }
+
+    /// Sampling ratio in `environment`. Explicit `sampling_ratio` always wins, otherwise sample
+    /// every trace outside production and [PRODUCTION_SAMPLING_RATIO] in production.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::config::Environment;
+    /// # use avantis_utils::telemetry::TelemetrySetting;
+    /// # fn example(setting: TelemetrySetting) {
+    /// let ratio = setting.sampling_ratio_for(Environment::Production);
+    /// # }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn sampling_ratio_for(&self, environment: Environment) -> f64 {
+        self.sampling_ratio
+            .unwrap_or(match environment.is_production() {
+                true => PRODUCTION_SAMPLING_RATIO,
+                false => 1.0,
+            })
+    }
+
+    /// Current environment, from `deployment_environment` if it is a valid [Environment],
+    /// otherwise from env `APP_ENVIRONMENT`.
+    #[cfg(feature = "config")]
+    fn environment(&self) -> Environment {
+        self.deployment_environment
+            .clone()
+            .map(Environment::try_from)
+            .unwrap_or_else(Environment::from_env)
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "config")]
+    fn sampling_ratio(&self) -> f64 {
+        self.sampling_ratio_for(self.environment())
+    }
+
+    #[cfg(not(feature = "config"))]
+    fn sampling_ratio(&self) -> f64 {
+        self.sampling_ratio.unwrap_or(1.0)
+    }
+
+    /// Log level in `environment`. Explicit `log_level` always wins, otherwise
+    /// [DEVELOPMENT_LOG_LEVEL] outside production and [PRODUCTION_LOG_LEVEL] in production.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use avantis_utils::config::Environment;
+    /// # use avantis_utils::telemetry::TelemetrySetting;
+    /// # fn example(setting: TelemetrySetting) {
+    /// let log_level = setting.log_level_for(Environment::Develop);
+    /// # }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn log_level_for(&self, environment: Environment) -> String {
+        match (self.log_level.trim(), environment.is_production()) {
+            ("", true) => PRODUCTION_LOG_LEVEL.to_string(),
+            ("", false) => DEVELOPMENT_LOG_LEVEL.to_string(),
+            (log_level, _) => log_level.to_string(),
+        }
+    }
+
+    #[cfg(feature = "config")]
+    fn log_level(&self) -> String {
+        self.log_level_for(self.environment())
+    }
+
+    #[cfg(not(feature = "config"))]
+    fn log_level(&self) -> String {
+        match self.log_level.trim() {
+            "" => PRODUCTION_LOG_LEVEL.to_string(),
+            log_level => log_level.to_string(),
+        }
+    }
+
+    fn tracer_provider(&self, service_name: &'static str) -> trace::TracerProvider {
+        let otlp_exporter = SpanExporterBuilder::from(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(self.otel_collector_endpoint.clone()),
+        )
+        .build_span_exporter()
+        .unwrap();
+        let otlp_exporter = CircuitBreakerExporter::new(
+            otlp_exporter,
+            self.export_failure_threshold,
+            Duration::from_secs(self.export_cooldown_seconds),
+        );
+
+        let mut provider_builder = trace::TracerProvider::builder()
+            .with_config(
+                trace::config()
+                    .with_resource(self.resource(service_name))
+                    .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                        self.sampling_ratio(),
+                    )))),
             )
-            .install_batch(opentelemetry::runtime::Tokio)
-            .unwrap();
+            .with_batch_exporter(otlp_exporter, opentelemetry::runtime::Tokio);
 
-        tracing_opentelemetry::layer().with_tracer(tracer)
+        if self.otel_debug {
+            provider_builder = provider_builder
+                .with_simple_exporter(stdout::Exporter::new(std::io::stdout(), true));
+        }
+
+        provider_builder.build()
+    }
+
+    /// Build the telemetry subscriber without installing it as global default.
+    /// Use with [with_telemetry] to scope it, ie. in tests running multiple telemetry setups.
+    ///
+    /// Its tracer provider lives as long as the subscriber and is not installed globally.
+    /// Must be called within a tokio runtime.
+    pub fn build_subscriber(&self, service_name: &'static str) -> impl Subscriber + Send + Sync {
+        self.subscriber_with(service_name, self.tracer_provider(service_name))
     }
 
-    fn subscriber(&self, service_name: &'static str) -> impl Subscriber {
+    fn subscriber_with(
+        &self,
+        service_name: &'static str,
+        provider: trace::TracerProvider,
+    ) -> impl Subscriber + Send + Sync {
+        let tracer =
+            provider.versioned_tracer("opentelemetry-otlp", Some(env!("CARGO_PKG_VERSION")), None);
+
         Registry::default()
             .with(self.log_level_filter())
             .with(self.disable_targets_filter())
             .with(JsonStorageLayer)
             .with(self.bunyan_formatter(service_name))
-            .with(self.tracer(service_name))
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .with(TracerProviderLayer(Some(provider)))
     }
 
     pub fn init_telemetry(&self, service_name: &'static str) -> Result<(), Error> {
         LogTracer::init().map_err(|_| Error::TelemetryAlreadyInit)?;
         set_text_map_propagator(TraceContextPropagator::new());
-        set_global_default(self.subscriber(service_name))
+
+        let provider = self.tracer_provider(service_name);
+        let _ = set_tracer_provider(provider.clone());
+        set_global_default(self.subscriber_with(service_name, provider))
             .map_err(|_| Error::TelemetryAlreadyInit)?;
 
+        if self.panic_hook {
+            install_panic_hook();
+        }
+
         info!(
             "initializing telemetry with log level [{}]: Done",
-            self.log_level
+            self.log_level()
         );
 
         Ok(())
     }
 }
 
+/// Keep the tracer provider of a subscriber alive as long as the subscriber, as tracers only
+/// hold a weak reference to it. Shutting down the provider blocks until its batch exporter task
+/// flushes, so it is shut down off the runtime thread, which would otherwise deadlock on a
+/// current thread runtime.
+struct TracerProviderLayer(Option<trace::TracerProvider>);
+
+impl<S: Subscriber> Layer<S> for TracerProviderLayer {}
+
+impl Drop for TracerProviderLayer {
+    fn drop(&mut self) {
+        let provider = self.0.take();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn_blocking(move || drop(provider));
+        }
+    }
+}
+
+/// Run `future` with `subscriber` as its default subscriber, without touching the global default.
+/// See [TelemetrySetting::build_subscriber].
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::telemetry::{with_telemetry, TelemetrySetting};
+/// # async fn example(setting: TelemetrySetting) {
+/// let subscriber = setting.build_subscriber("my-service");
+///
+/// with_telemetry(subscriber, async {
+///     tracing::info!("logged with telemetry subscriber");
+/// })
+/// .await;
+/// # }
+/// ```
+pub async fn with_telemetry<S, F>(subscriber: S, future: F) -> F::Output
+where
+    S: Subscriber + Send + Sync + 'static,
+    F: Future,
+{
+    future.with_subscriber(subscriber).await
+}
+
+/// Replace panic hook with one logging panics as structured `error` events, with panic
+/// message, location and trace id of the current span. Called by
+/// [TelemetrySetting::init_telemetry] unless `panic_hook` is disabled.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let trace_id = tracing::Span::current()
+            .context()
+            .span()
+            .span_context()
+            .trace_id();
+
+        error!(
+            panic.message = panic_message(info.payload()),
+            panic.location = %location,
+            trace_id = %trace_id,
+            "panic occurred"
+        );
+    }));
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| {
+            payload
+                .downcast_ref::<String>()
+                .map(|message| message.as_str())
+        })
+        .unwrap_or("Box<dyn Any>")
+}
+
+/// Record `err` as an `error` event of `span`, with its message, each level of its source
+/// chain and its backtrace, so the full cause chain shows up in logs and traces. Fields
+/// follow OpenTelemetry exception conventions:
+///
+/// - `exception.message`: top level message.
+/// - `exception.chain`: messages of every level, outermost first.
+/// - `exception.stacktrace`: backtrace, if captured. Set env `RUST_BACKTRACE=1` or
+///   `RUST_LIB_BACKTRACE=1` to capture backtraces of `anyhow::Error`.
+///
+/// # Example
+///
+/// ```
+/// # use anyhow::Context;
+/// # use avantis_utils::telemetry::record_error;
+/// let span = tracing::info_span!("load_portfolio");
+/// let err = std::fs::read("missing.json")
+///     .context("unable to load portfolio")
+///     .unwrap_err();
+///
+/// record_error(&span, &err);
+/// ```
+pub fn record_error(span: &tracing::Span, err: &anyhow::Error) {
+    let chain = error_chain(err);
+    let backtrace = captured_backtrace(err);
+
+    error!(
+        parent: span,
+        exception.message = %err,
+        exception.chain = ?chain,
+        exception.stacktrace = backtrace.as_deref(),
+        "{:#}",
+        err
+    );
+}
+
+/// Messages of `err` and its sources, outermost first.
+fn error_chain(err: &anyhow::Error) -> Vec<String> {
+    err.chain().map(|cause| cause.to_string()).collect()
+}
+
+fn captured_backtrace(err: &anyhow::Error) -> Option<String> {
+    let backtrace = err.backtrace();
+    match backtrace.status() {
+        std::backtrace::BacktraceStatus::Captured => Some(backtrace.to_string()),
+        _ => None,
+    }
+}
+
+/// Return `true` if `target` or any of its parent modules is in `disabled_targets`.
+/// For example, `h2` disables both `h2` and `h2::client::connection`, but not `h2c`.
+fn is_target_disabled(disabled_targets: &HashSet<String>, target: &str) -> bool {
+    disabled_targets.contains(target)
+        || target
+            .match_indices("::")
+            .any(|(index, _)| disabled_targets.contains(&target[..index]))
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("telemetry already initialized")]
     TelemetryAlreadyInit,
 }
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::Key;
+    use serial_test::serial;
+
+    use super::*;
+
+    fn setting() -> TelemetrySetting {
+        TelemetrySetting {
+            otel_collector_endpoint: "http://localhost:4317".to_string(),
+            disabled_targets: HashSet::new(),
+            log_level: "info".to_string(),
+            targets: HashMap::new(),
+            otel_debug: false,
+            host_name: None,
+            service_version: None,
+            deployment_environment: None,
+            resource_attributes: HashMap::new(),
+            panic_hook: true,
+            sampling_ratio: None,
+            export_failure_threshold: 3,
+            export_cooldown_seconds: 60,
+            include_location: false,
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct BufferWriter(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for BufferWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_with(setting: &TelemetrySetting) -> String {
+        let writer = BufferWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = Registry::default()
+            .with(JsonStorageLayer)
+            .with(setting.bunyan_formatter_with_writer("my-service", move || make_writer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || info!("log with location"));
+
+        let output = writer.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_include_location() {
+        let log = log_with(&setting());
+
+        assert!(log.contains(r#""msg":"log with location""#));
+        assert!(!log.contains(r#""file":"#));
+        assert!(!log.contains(r#""line":"#));
+
+        let log = log_with(&TelemetrySetting {
+            include_location: true,
+            ..setting()
+        });
+
+        assert!(log.contains(r#""file":"src/telemetry.rs""#));
+        assert!(log.contains(r#""line":"#));
+    }
+
+    #[tokio::test]
+    async fn test_with_telemetry() {
+        for _ in 0..2 {
+            let subscriber = setting().build_subscriber("my-service");
+
+            let enabled = with_telemetry(subscriber, async {
+                !tracing::info_span!("span").is_disabled()
+            })
+            .await;
+
+            assert!(enabled);
+        }
+
+        assert!(tracing::info_span!("span").is_disabled());
+    }
+
+    #[test]
+    fn test_error_chain() {
+        let err = anyhow::Error::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "file not found",
+        ))
+        .context("unable to read portfolio")
+        .context("unable to load account 1");
+
+        assert_eq!(
+            vec![
+                "unable to load account 1",
+                "unable to read portfolio",
+                "file not found"
+            ],
+            error_chain(&err)
+        );
+        assert_eq!(
+            vec!["single level"],
+            error_chain(&anyhow::anyhow!("single level"))
+        );
+
+        record_error(&tracing::info_span!("span"), &err);
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("static message")).unwrap_err();
+        assert_eq!("static message", panic_message(payload.as_ref()));
+
+        let payload = std::panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+        assert_eq!("formatted 1", panic_message(payload.as_ref()));
+
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(1)).unwrap_err();
+        assert_eq!("Box<dyn Any>", panic_message(payload.as_ref()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_host_name() {
+        let previous_hostname = std::env::var("HOSTNAME");
+        std::env::set_var("HOSTNAME", "my-host");
+
+        assert_eq!("my-host", setting().host_name());
+
+        std::env::set_var("POD_NAME", "my-pod");
+
+        assert_eq!("my-pod", setting().host_name());
+
+        let setting = TelemetrySetting {
+            host_name: Some("my-service-1".to_string()),
+            ..setting()
+        };
+
+        assert_eq!("my-service-1", setting.host_name());
+
+        std::env::remove_var("POD_NAME");
+        match previous_hostname {
+            Ok(hostname) => std::env::set_var("HOSTNAME", hostname),
+            Err(_) => std::env::remove_var("HOSTNAME"),
+        }
+    }
+
+    #[test]
+    fn test_log_directives() {
+        assert_eq!("info", setting().log_directives());
+
+        let setting = TelemetrySetting {
+            targets: HashMap::from([
+                ("sqlx".to_string(), "warn".to_string()),
+                ("my_app".to_string(), "debug".to_string()),
+            ]),
+            ..setting()
+        };
+
+        assert_eq!("info,my_app=debug,sqlx=warn", setting.log_directives());
+    }
+
+    #[test]
+    fn test_is_target_disabled() {
+        let disabled_targets = HashSet::from(["h2".to_string(), "hyper::client::pool".to_string()]);
+
+        assert!(is_target_disabled(&disabled_targets, "h2"));
+        assert!(is_target_disabled(
+            &disabled_targets,
+            "h2::client::connection"
+        ));
+        assert!(is_target_disabled(&disabled_targets, "hyper::client::pool"));
+        assert!(is_target_disabled(
+            &disabled_targets,
+            "hyper::client::pool::inner"
+        ));
+
+        assert!(!is_target_disabled(&disabled_targets, "h2c"));
+        assert!(!is_target_disabled(&disabled_targets, "hyper"));
+        assert!(!is_target_disabled(&disabled_targets, "hyper::client"));
+        assert!(!is_target_disabled(
+            &disabled_targets,
+            "hyper::client::pooled"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_resource() {
+        let setting = TelemetrySetting {
+            service_version: Some("1.2.3".to_string()),
+            deployment_environment: Some("production".to_string()),
+            resource_attributes: HashMap::from([("team".to_string(), "genesis".to_string())]),
+            ..setting()
+        };
+
+        let resource = setting.resource("my-service");
+
+        assert_eq!(
+            Some("my-service".into()),
+            resource.get(Key::new("service.name"))
+        );
+        assert_eq!(
+            Some("1.2.3".into()),
+            resource.get(Key::new("service.version"))
+        );
+        assert_eq!(
+            Some("production".into()),
+            resource.get(Key::new("deployment.environment"))
+        );
+        assert_eq!(Some("genesis".into()), resource.get(Key::new("team")));
+    }
+
+    #[test]
+    #[serial]
+    fn test_resource_from_env() {
+        let previous_environment = std::env::var("APP_ENVIRONMENT");
+        std::env::set_var("APP_ENVIRONMENT", "develop");
+
+        let resource = setting().resource("my-service");
+
+        match previous_environment {
+            Ok(environment) => std::env::set_var("APP_ENVIRONMENT", environment),
+            Err(_) => std::env::remove_var("APP_ENVIRONMENT"),
+        }
+
+        assert_eq!(
+            Some(env!("CARGO_PKG_VERSION").into()),
+            resource.get(Key::new("service.version"))
+        );
+        assert_eq!(
+            Some("develop".into()),
+            resource.get(Key::new("deployment.environment"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_sampling_ratio_for() {
+        assert_eq!(1.0, setting().sampling_ratio_for(Environment::Develop));
+        assert_eq!(1.0, setting().sampling_ratio_for(Environment::Local));
+        assert_eq!(
+            PRODUCTION_SAMPLING_RATIO,
+            setting().sampling_ratio_for(Environment::Production)
+        );
+
+        let setting = TelemetrySetting {
+            sampling_ratio: Some(0.5),
+            ..setting()
+        };
+
+        assert_eq!(0.5, setting.sampling_ratio_for(Environment::Develop));
+        assert_eq!(0.5, setting.sampling_ratio_for(Environment::Production));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(feature = "config")]
+    fn test_sampling_ratio() {
+        let previous_environment = std::env::var("APP_ENVIRONMENT");
+        std::env::set_var("APP_ENVIRONMENT", "production");
+
+        let from_env = setting().sampling_ratio();
+        let from_setting = TelemetrySetting {
+            deployment_environment: Some("develop".to_string()),
+            ..setting()
+        }
+        .sampling_ratio();
+
+        match previous_environment {
+            Ok(environment) => std::env::set_var("APP_ENVIRONMENT", environment),
+            Err(_) => std::env::remove_var("APP_ENVIRONMENT"),
+        }
+
+        assert_eq!(PRODUCTION_SAMPLING_RATIO, from_env);
+        assert_eq!(1.0, from_setting);
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_log_level_for() {
+        let setting = TelemetrySetting {
+            log_level: "".to_string(),
+            targets: HashMap::from([("sqlx".to_string(), "warn".to_string())]),
+            ..setting()
+        };
+
+        assert_eq!("debug", setting.log_level_for(Environment::Local));
+        assert_eq!("debug", setting.log_level_for(Environment::Develop));
+        assert_eq!("info", setting.log_level_for(Environment::Production));
+
+        let setting = TelemetrySetting {
+            deployment_environment: Some("production".to_string()),
+            ..setting
+        };
+
+        assert_eq!("info,sqlx=warn", setting.log_directives());
+
+        let setting = TelemetrySetting {
+            log_level: "warn".to_string(),
+            ..setting
+        };
+
+        assert_eq!("warn", setting.log_level_for(Environment::Develop));
+        assert_eq!("warn", setting.log_level_for(Environment::Production));
+    }
+}
@@ -0,0 +1,71 @@
+//! Correlation id propagation. Join logs and traces from db, redis and kafka layers
+//! handling the same request.
+//!
+//! Correlation id is kept in OpenTelemetry baggage of the current context and recorded
+//! as `correlation_id` field of a wrapping span.
+
+use std::future::Future;
+
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::trace::FutureExt;
+use opentelemetry::Context;
+use opentelemetry::KeyValue;
+use tracing::Instrument;
+
+/// Baggage key of correlation id.
+pub const CORRELATION_ID_KEY: &str = "correlation_id";
+
+/// Kafka header name of correlation id.
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+/// Run `future` with given correlation id.
+///
+/// # Example
+///
+/// ```
+/// # use avantis_utils::telemetry::correlation::{current_correlation_id, with_correlation_id};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let id = with_correlation_id("request-1", async { current_correlation_id() }).await;
+///
+/// assert_eq!(Some("request-1".to_string()), id);
+/// # }
+/// ```
+pub fn with_correlation_id<F: Future>(
+    id: impl Into<String>,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    let id = id.into();
+    let span = tracing::info_span!("correlation", correlation_id = %id);
+    let cx = Context::current_with_baggage(vec![KeyValue::new(CORRELATION_ID_KEY, id)]);
+
+    future.instrument(span).with_context(cx)
+}
+
+/// Correlation id of the current context, if any.
+pub fn current_correlation_id() -> Option<String> {
+    Context::current()
+        .baggage()
+        .get(CORRELATION_ID_KEY)
+        .map(|id| id.as_str().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_correlation_id() {
+        assert_eq!(None, current_correlation_id());
+
+        let id = with_correlation_id("outer", async {
+            let inner = with_correlation_id("inner", async { current_correlation_id() }).await;
+
+            (current_correlation_id(), inner)
+        })
+        .await;
+
+        assert_eq!((Some("outer".to_string()), Some("inner".to_string())), id);
+        assert_eq!(None, current_correlation_id());
+    }
+}
@@ -0,0 +1,164 @@
+//! Span exporter degrading gracefully when the collector is down.
+//!
+//! Without it, every batch exported to an unreachable collector waits for the export timeout
+//! and logs an error. [CircuitBreakerExporter] stops exporting for a cooldown after repeated
+//! failures, dropping spans with a single warning, then tries again.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use opentelemetry::sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use tracing::{info, warn};
+
+/// Wrap `inner` exporter. After `failure_threshold` consecutive failed exports, drop spans
+/// without exporting for `cooldown`, then export again. Only the export tripping the breaker
+/// is reported, as a warning, instead of an error per batch.
+///
+/// Used by [TelemetrySetting::init_telemetry](super::TelemetrySetting::init_telemetry), see
+/// `export_failure_threshold` and `export_cooldown_seconds`.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use opentelemetry::sdk::export::trace::stdout;
+/// # use avantis_utils::telemetry::exporter::CircuitBreakerExporter;
+/// let exporter = CircuitBreakerExporter::new(
+///     stdout::Exporter::new(std::io::stdout(), true),
+///     3,
+///     Duration::from_secs(60),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct CircuitBreakerExporter<E> {
+    inner: E,
+    failure_threshold: u32,
+    cooldown: Duration,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl<E> CircuitBreakerExporter<E> {
+    pub fn new(inner: E, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner,
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            consecutive_failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Whether exports are currently skipped.
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(Instant::now())
+    }
+
+    fn is_open_at(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|open_until| now < open_until)
+    }
+
+    fn record_at(&mut self, result: ExportResult, now: Instant) -> ExportResult {
+        match result {
+            Ok(()) => {
+                if self.open_until.take().is_some() {
+                    info!("span export recovered, resume exporting to collector");
+                }
+                self.consecutive_failures = 0;
+                Ok(())
+            }
+            Err(err) => {
+                self.consecutive_failures += 1;
+                if self.consecutive_failures < self.failure_threshold {
+                    return Err(err);
+                }
+
+                self.consecutive_failures = 0;
+                self.open_until = Some(now + self.cooldown);
+                warn!(
+                    "span export failed repeatedly, drop spans for {:?}: {}",
+                    self.cooldown, err
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<E> SpanExporter for CircuitBreakerExporter<E>
+where
+    E: SpanExporter,
+{
+    async fn export(&mut self, batch: Vec<SpanData>) -> ExportResult {
+        if self.is_open() {
+            return Ok(());
+        }
+
+        let result = self.inner.export(batch).await;
+        self.record_at(result, Instant::now())
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::TraceError;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct FailingExporter {
+        calls: usize,
+    }
+
+    #[async_trait]
+    impl SpanExporter for FailingExporter {
+        async fn export(&mut self, _: Vec<SpanData>) -> ExportResult {
+            self.calls += 1;
+            Err(TraceError::from("collector unreachable"))
+        }
+    }
+
+    #[test]
+    fn test_record_at() {
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(60);
+        let mut exporter = CircuitBreakerExporter::new(FailingExporter::default(), 2, cooldown);
+
+        assert!(exporter
+            .record_at(Err(TraceError::from("failed")), now)
+            .is_err());
+        assert!(!exporter.is_open_at(now));
+
+        assert!(exporter
+            .record_at(Err(TraceError::from("failed")), now)
+            .is_ok());
+        assert!(exporter.is_open_at(now));
+        assert!(exporter.is_open_at(now + cooldown / 2));
+        assert!(!exporter.is_open_at(now + cooldown));
+
+        assert!(exporter.record_at(Ok(()), now + cooldown).is_ok());
+        assert!(!exporter.is_open_at(now + cooldown));
+        assert_eq!(0, exporter.consecutive_failures);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let mut exporter =
+            CircuitBreakerExporter::new(FailingExporter::default(), 2, Duration::from_secs(60));
+
+        assert!(exporter.export(vec![]).await.is_err());
+        assert!(exporter.export(vec![]).await.is_ok());
+        assert!(exporter.is_open());
+
+        for _ in 0..5 {
+            assert!(exporter.export(vec![]).await.is_ok());
+        }
+
+        assert_eq!(2, exporter.inner.calls);
+    }
+}
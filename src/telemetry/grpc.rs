@@ -0,0 +1,186 @@
+//! Trace propagation over gRPC metadata for tonic clients and servers.
+//! Require `telemetry-grpc` feature.
+//!
+//! Mirror trace header handling of kafka producer and consumer, using the propagator set by
+//! [init_telemetry](super::TelemetrySetting::init_telemetry).
+//!
+//! Server interceptors run before handler spans exist, so [extract_trace] only stores the
+//! caller trace in the request. Call [set_trace] in the instrumented handler to join it.
+//!
+//! # Example
+//!
+//! ```
+//! # use avantis_utils::telemetry::grpc::{extract_trace, inject_trace, set_trace};
+//! # use tonic::service::Interceptor;
+//! # use tonic::{Request, Response, Status};
+//! fn use_interceptor(_: impl Interceptor) {}
+//!
+//! // client, ie. `MyClient::with_interceptor(channel, inject_trace)`
+//! use_interceptor(inject_trace);
+//!
+//! // server, ie. `MyServer::with_interceptor(service, extract_trace)`
+//! use_interceptor(extract_trace);
+//!
+//! #[tracing::instrument(skip_all)]
+//! async fn handler(request: Request<String>) -> Result<Response<String>, Status> {
+//!     set_trace(&request);
+//!
+//!     Ok(Response::new(request.into_inner()))
+//! }
+//! ```
+
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::Context;
+use tonic::metadata::{KeyRef, MetadataKey, MetadataMap, MetadataValue};
+use tonic::{Request, Status};
+use tracing::warn;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Client interceptor. Inject trace context of current span into request metadata.
+#[allow(clippy::result_large_err)] // signature required by tonic interceptor
+pub fn inject_trace(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let cx = tracing::Span::current().context();
+
+    global::get_text_map_propagator(|prop| {
+        prop.inject_context(&cx, &mut MetadataInjector(request.metadata_mut()))
+    });
+
+    Ok(request)
+}
+
+/// Server interceptor. Extract trace context from request metadata and store it in request
+/// extensions, for [set_trace] in the handler.
+#[allow(clippy::result_large_err)] // signature required by tonic interceptor
+pub fn extract_trace(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let parent_cx = extract_context(request.metadata());
+    request.extensions_mut().insert(TraceParent(parent_cx));
+
+    Ok(request)
+}
+
+/// Set parent of current span to the caller trace, as stored by [extract_trace], or from
+/// `traceparent` and `tracestate` metadata of the request without the interceptor.
+/// Call at the start of an instrumented handler to join the caller trace.
+pub fn set_trace<T>(request: &Request<T>) {
+    let parent_cx = match request.extensions().get::<TraceParent>() {
+        Some(TraceParent(parent_cx)) => parent_cx.clone(),
+        None => extract_context(request.metadata()),
+    };
+
+    tracing::Span::current().set_parent(parent_cx);
+}
+
+/// Trace context of the caller, stored in request extensions by [extract_trace].
+#[derive(Clone, Debug)]
+struct TraceParent(Context);
+
+fn extract_context(metadata: &MetadataMap) -> Context {
+    global::get_text_map_propagator(|prop| prop.extract(&MetadataExtractor(metadata)))
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl<'a> Injector for MetadataInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        match (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::from_str(&value),
+        ) {
+            (Ok(key), Ok(value)) => {
+                self.0.insert(key, value);
+            }
+            _ => warn!("unable to inject trace metadata `{}`", key),
+        }
+    }
+}
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl<'a> Extractor for MetadataExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|key| match key {
+                KeyRef::Ascii(key) => Some(key.as_str()),
+                KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::sdk::propagation::TraceContextPropagator;
+    use opentelemetry::trace::{SpanContext, TraceContextExt, TraceFlags, TraceState};
+    use opentelemetry::trace::{SpanId, TraceId};
+
+    use super::*;
+
+    #[test]
+    fn test_inject_and_extract() {
+        let propagator = TraceContextPropagator::new();
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(span_context);
+
+        let mut metadata = MetadataMap::new();
+        propagator.inject_context(&cx, &mut MetadataInjector(&mut metadata));
+
+        assert_eq!(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            metadata.get("traceparent").unwrap().to_str().unwrap()
+        );
+
+        let extracted = propagator.extract(&MetadataExtractor(&metadata));
+
+        assert_eq!(
+            cx.span().span_context().trace_id(),
+            extracted.span().span_context().trace_id()
+        );
+    }
+
+    #[test]
+    fn test_handler_span_joins_caller_trace() {
+        use opentelemetry::trace::TracerProvider;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = opentelemetry::sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert(
+            "traceparent",
+            MetadataValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let mut request = extract_trace(request).unwrap();
+        // joined from request extensions, not metadata.
+        request.metadata_mut().remove("traceparent");
+
+        let trace_id = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("handler");
+            let _entered = span.enter();
+
+            set_trace(&request);
+
+            span.context().span().span_context().trace_id()
+        });
+
+        assert_eq!(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            trace_id
+        );
+    }
+}
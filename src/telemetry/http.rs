@@ -0,0 +1,119 @@
+//! Tower layer tracing HTTP requests, ie. for axum servers. Require `telemetry-http` feature.
+//!
+//! Start a span per request with parent extracted from incoming `traceparent` header,
+//! using the propagator set by [init_telemetry](super::TelemetrySetting::init_telemetry).
+//! Status code and latency are recorded to the span on response.
+//!
+//! # Example
+//!
+//! ```
+//! # use avantis_utils::telemetry::http::trace_layer;
+//! let layer = trace_layer();
+//!
+//! // add to your axum router, ie. `Router::new().route("/", get(handler)).layer(layer)`
+//! ```
+
+use std::time::Duration;
+
+use http::{HeaderMap, Request, Response};
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{DefaultOnRequest, MakeSpan, OnResponse, TraceLayer};
+use tracing::field::Empty;
+use tracing::{info, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// [TraceLayer] returned by [trace_layer].
+pub type HttpTraceLayer = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    MakeRequestSpan,
+    DefaultOnRequest,
+    RecordResponse,
+>;
+
+/// Create a [TraceLayer] for HTTP servers. See [module level docs](self).
+pub fn trace_layer() -> HttpTraceLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(MakeRequestSpan)
+        .on_response(RecordResponse)
+}
+
+/// Create a span per request, with parent from incoming trace context headers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MakeRequestSpan;
+
+impl<B> MakeSpan<B> for MakeRequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let span = tracing::info_span!(
+            "http_request",
+            otel.kind = "server",
+            http.method = %request.method(),
+            http.target = %request.uri(),
+            http.status_code = Empty,
+            http.latency_ms = Empty,
+        );
+
+        let parent_cx = global::get_text_map_propagator(|prop| {
+            prop.extract(&HeaderExtractor(request.headers()))
+        });
+        span.set_parent(parent_cx);
+
+        span
+    }
+}
+
+/// Record status code and latency of response to request span.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecordResponse;
+
+impl<B> OnResponse<B> for RecordResponse {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        span.record("http.status_code", response.status().as_u16());
+        span.record("http.latency_ms", latency.as_millis() as u64);
+
+        info!(
+            "finished processing request with status [{}]",
+            response.status()
+        );
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::sdk::propagation::TraceContextPropagator;
+    use opentelemetry::trace::TraceContextExt;
+
+    use super::*;
+
+    #[test]
+    fn test_header_extractor() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let cx = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+
+        assert_eq!(
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+            cx.span().span_context().trace_id().to_string()
+        );
+    }
+}
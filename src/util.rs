@@ -0,0 +1,3 @@
+//! Small utilities shared by other modules.
+
+pub mod retry;
@@ -0,0 +1,217 @@
+//! Retry async operations with exponential backoff.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use avantis_utils::util::retry::{retry_async, RetryPolicy};
+//! # #[tokio::main]
+//! # async fn main() {
+//! let policy = RetryPolicy {
+//!     max_attempts: 3,
+//!     base_delay: Duration::from_millis(10),
+//!     ..RetryPolicy::default()
+//! };
+//!
+//! let result: Result<(), std::io::Error> = retry_async(
+//!     &policy,
+//!     |err: &std::io::Error| err.kind() == std::io::ErrorKind::ConnectionRefused,
+//!     || async { Ok(()) },
+//! )
+//! .await;
+//! # }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tracing::debug;
+
+/// Controls how [retry_async] retries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one. `0` is treated as `1`.
+    pub max_attempts: u32,
+    /// Delay before the second attempt. Doubled for each following attempt.
+    pub base_delay: Duration,
+    /// Upper bound of delay between attempts, before jitter.
+    pub max_delay: Duration,
+    /// Fraction of each delay to randomize, from `0.0` (no jitter) to `1.0` (full jitter).
+    /// ie. `0.5` sleeps between 50% and 100% of the delay.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to sleep after failed attempt number `attempt`, starting from `1`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+
+        delay.mul_f64(1.0 - jitter * random_fraction())
+    }
+}
+
+/// Run `op` until it succeeds, `retryable` returns `false` for its error, or
+/// `policy.max_attempts` is reached. Return result of the last attempt.
+///
+/// Sleep between attempts per [RetryPolicy::delay], never after the last attempt.
+/// Cancellation safe: dropping the returned future stops retrying, with no state left behind.
+pub async fn retry_async<T, E, F, Fut, R>(
+    policy: &RetryPolicy,
+    retryable: R,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    R: Fn(&E) -> bool,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(err) if attempt < max_attempts && retryable(&err) => {
+                let delay = policy.delay(attempt);
+                debug!(attempt, ?delay, "retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Random number in `[0, 1)`, good enough for jitter without a `rand` dependency.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = policy(10);
+
+        assert_eq!(Duration::from_millis(1), policy.delay(1));
+        assert_eq!(Duration::from_millis(2), policy.delay(2));
+        assert_eq!(Duration::from_millis(4), policy.delay(3));
+        assert_eq!(Duration::from_millis(5), policy.delay(4));
+        assert_eq!(Duration::from_millis(5), policy.delay(u32::MAX));
+    }
+
+    #[test]
+    fn test_delay_with_jitter() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..policy(10)
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(3);
+            assert!(delay > Duration::from_millis(2) && delay <= Duration::from_millis(4));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let calls = AtomicU32::new(0);
+
+        let result = retry_async(
+            &policy(3),
+            |_: &&str| true,
+            || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err("fail"),
+                    _ => Ok("success"),
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(Ok("success"), result);
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted() {
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), u32> = retry_async(
+            &policy(3),
+            |_| true,
+            || async { Err(calls.fetch_add(1, Ordering::SeqCst)) },
+        )
+        .await;
+
+        assert_eq!(Err(2), result);
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_retry_non_retryable() {
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), &str> = retry_async(
+            &policy(3),
+            |err| *err != "fatal",
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err("fatal")
+            },
+        )
+        .await;
+
+        assert_eq!(Err("fatal"), result);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_no_sleep_after_last_attempt() {
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::from_secs(60),
+            ..RetryPolicy::default()
+        };
+
+        let started_at = std::time::Instant::now();
+        let result: Result<(), &str> =
+            retry_async(&policy, |_| true, || async { Err("fail") }).await;
+
+        assert_eq!(Err("fail"), result);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -1,8 +1,12 @@
 #![cfg(all(feature = "redis", feature = "config"))]
 
 use ::redis_rs::AsyncCommands;
+use avantis_utils::redis::Error;
 use avantis_utils::redis::GetOrFetchExt;
 use avantis_utils::redis::GetOrRefreshExt;
+use avantis_utils::redis::GetWithTtlExt;
+use avantis_utils::redis::RedisConfig;
+use avantis_utils::redis::RedisQueue;
 use avantis_utils::redis::Result;
 use serial_test::serial;
 use tokio;
@@ -256,10 +260,10 @@ async fn test_get_or_refresh() -> Result<()> {
     for n in 1..4 {
         computation::wait_expire(expire_seconds).await;
 
-        let result = connection::get_redis_connection()
+        let (result, handle) = connection::get_redis_connection()
             .await
             .unwrap()
-            .get_or_refresh(
+            .get_or_refresh_with_handle(
                 key,
                 move || async move { computation::simple(n).await },
                 expire_seconds as usize,
@@ -275,9 +279,11 @@ async fn test_get_or_refresh() -> Result<()> {
             result,
         );
 
-        // This assertion might be flaky depends on if redis successfully cached data within
-        // wait_simple timeframe. If this happens, kindly increase BUFFER_COMPUTATION_TIME_MILLIS
-        computation::wait_simple().await;
+        handle
+            .expect("Should refresh expired data in background")
+            .await
+            .unwrap()
+            .unwrap();
         let result: Option<String> = connection.hget(key, "value").await.unwrap();
         let result = result.unwrap();
         assert_eq!(
@@ -349,6 +355,243 @@ async fn test_get_or_refresh() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[serial]
+async fn test_get_with_ttl() -> Result<()> {
+    let mut connection = connection::get_redis_connection().await.unwrap();
+
+    let key = "TEST_GET_WITH_TTL";
+
+    // Test that missing key return None
+
+    let _: () = connection.del(key).await.unwrap();
+
+    let result: Option<(String, i64)> = connection.get_with_ttl(key).await.unwrap();
+    assert_eq!(None, result, "Should return None for missing key");
+
+    // Test that get_or_fetch layout return TTL
+
+    let expire_seconds = 1000;
+
+    connection
+        .get_or_fetch(
+            key,
+            || async { computation::simple(0).await },
+            expire_seconds,
+        )
+        .await
+        .unwrap();
+
+    let (result, ttl): (String, i64) = connection.get_with_ttl(key).await.unwrap().unwrap();
+    assert_eq!(computation::result(0), result);
+    assert!(
+        0 < ttl && ttl <= expire_seconds as i64,
+        "Should return remaining ttl. Got {}",
+        ttl
+    );
+
+    // Test that key without expiry return -1
+
+    let _: () = connection.set(key, computation::result(1)).await.unwrap();
+
+    let (result, ttl): (String, i64) = connection.get_with_ttl(key).await.unwrap().unwrap();
+    assert_eq!(computation::result(1), result);
+    assert_eq!(-1, ttl, "Should return -1 for key without expiry");
+
+    // Test that zero expire_seconds set without expiry
+
+    let _: () = connection.del(key).await.unwrap();
+
+    connection
+        .get_or_fetch(key, || async { computation::simple(3).await }, 0)
+        .await
+        .unwrap();
+
+    let (result, ttl): (String, i64) = connection.get_with_ttl(key).await.unwrap().unwrap();
+    assert_eq!(computation::result(3), result);
+    assert_eq!(-1, ttl, "Should not expire with zero expire_seconds");
+
+    let _: () = connection.del(key).await.unwrap();
+
+    connection::get_redis_connection()
+        .await
+        .unwrap()
+        .get_or_refresh(key, || async { computation::simple(4).await }, 0)
+        .await
+        .unwrap();
+
+    let (result, ttl): (String, i64) = connection.get_with_ttl(key).await.unwrap().unwrap();
+    assert_eq!(computation::result(4), result);
+    assert_eq!(-1, ttl, "Should not expire with zero expire_seconds");
+
+    // Test that get_or_refresh layout return TTL from expired_when
+
+    let _: () = connection.del(key).await.unwrap();
+
+    connection::get_redis_connection()
+        .await
+        .unwrap()
+        .get_or_refresh(
+            key,
+            || async { computation::simple(2).await },
+            expire_seconds,
+        )
+        .await
+        .unwrap();
+
+    let (result, ttl): (String, i64) = connection.get_with_ttl(key).await.unwrap().unwrap();
+    assert_eq!(computation::result(2), result);
+    assert!(
+        0 < ttl && ttl <= expire_seconds as i64,
+        "Should return remaining ttl. Got {}",
+        ttl
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_or_fetch_keyed() -> Result<()> {
+    let mut connection = connection::get_redis_connection().await.unwrap();
+
+    let keys = ["TEST_GET_OR_FETCH_KEYED_1", "TEST_GET_OR_FETCH_KEYED_2"];
+    let _: () = connection.del(&keys).await.unwrap();
+
+    let mut handles = vec![];
+    for (index, key) in keys.into_iter().enumerate() {
+        let mut connection = connection::get_redis_connection().await.unwrap();
+        handles.push(tokio::spawn(async move {
+            connection
+                .get_or_fetch_keyed(
+                    key,
+                    move || async move { computation::simple(index as i32).await },
+                    1000,
+                )
+                .await
+        }));
+    }
+
+    for (index, handle) in handles.into_iter().enumerate() {
+        let (key, value): (&str, String) = handle.await.unwrap()?;
+
+        assert_eq!(keys[index], key);
+        assert_eq!(computation::result(index as i32), value);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_init_pool_validated() -> Result<()> {
+    let pool = connection::get_redis_config().init_pool_validated().await?;
+    let _: () = pool.get().await?.set("TEST_INIT_POOL_VALIDATED", 1).await?;
+
+    let unreachable = RedisConfig::builder()
+        .hosts(&["127.0.0.1:1"])
+        .connection_timeout_seconds(1)
+        .build()?;
+
+    assert!(unreachable.init_pool_validated().await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_key_type_mismatch() -> Result<()> {
+    let mut connection = connection::get_redis_connection().await.unwrap();
+
+    let key = "TEST_KEY_TYPE_MISMATCH";
+
+    // Test that get_or_refresh fail on key written by get_or_fetch
+
+    let _: () = connection.del(key).await.unwrap();
+
+    connection
+        .get_or_fetch(key, || async { computation::simple(0).await }, 1000)
+        .await
+        .unwrap();
+
+    let result: Result<String> = connection::get_redis_connection()
+        .await
+        .unwrap()
+        .get_or_refresh(key, || async { computation::simple(1).await }, 1000)
+        .await;
+
+    assert!(
+        matches!(result, Err(Error::KeyTypeMismatch { ref key }) if key == "TEST_KEY_TYPE_MISMATCH"),
+        "Should return key type mismatch error. Got {:?}",
+        result
+    );
+
+    // Test that get_or_fetch fail on key written by get_or_refresh
+
+    let _: () = connection.del(key).await.unwrap();
+
+    connection::get_redis_connection()
+        .await
+        .unwrap()
+        .get_or_refresh(key, || async { computation::simple(0).await }, 1000)
+        .await
+        .unwrap();
+
+    let result: Result<String> = connection
+        .get_or_fetch(key, || async { computation::simple(1).await }, 1000)
+        .await;
+
+    assert!(
+        matches!(result, Err(Error::KeyTypeMismatch { ref key }) if key == "TEST_KEY_TYPE_MISMATCH"),
+        "Should return key type mismatch error. Got {:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_redis_queue() -> Result<()> {
+    let mut connection = connection::get_redis_connection().await.unwrap();
+    let queue = RedisQueue::new(connection::get_redis_pool().await.clone());
+
+    let key = "TEST_REDIS_QUEUE";
+    let processing = RedisQueue::processing_list(key);
+    let timeout = std::time::Duration::from_millis(100);
+
+    let _: () = connection.del(&[key, processing.as_str()]).await.unwrap();
+
+    // Test that items are dequeued in FIFO order and timeout on empty queue
+
+    queue.enqueue(key, &1).await?;
+    queue.enqueue(key, &2).await?;
+
+    assert_eq!(Some(1), queue.dequeue::<i32>(key, timeout).await?);
+    assert_eq!(Some(2), queue.dequeue::<i32>(key, timeout).await?);
+    assert_eq!(None, queue.dequeue::<i32>(key, timeout).await?);
+
+    // Test that reserved items are kept until acked, and recovered back to queue
+
+    queue.enqueue(key, &3).await?;
+    queue.enqueue(key, &4).await?;
+
+    let reserved = queue.dequeue_reliable::<i32>(key, timeout).await?.unwrap();
+    assert_eq!(3, reserved.item);
+    queue.ack(key, &reserved).await?;
+
+    let reserved = queue.dequeue_reliable::<i32>(key, timeout).await?.unwrap();
+    assert_eq!(4, reserved.item);
+
+    let processing_len: usize = connection.llen(&processing).await.unwrap();
+    assert_eq!(1, processing_len);
+
+    assert_eq!(1, queue.recover(key).await?);
+    assert_eq!(Some(4), queue.dequeue::<i32>(key, timeout).await?);
+
+    Ok(())
+}
+
 mod computation {
     use std::time::Duration;
 
@@ -379,10 +622,6 @@ mod computation {
         sleep(Duration::from_secs(cache_ttl as u64)).await;
         sleep(Duration::from_millis(BUFFER_COMPUTATION_TIME_MILLIS)).await;
     }
-
-    pub(super) async fn wait_simple() {
-        tokio::time::sleep(Duration::from_millis(BUFFER_COMPUTATION_TIME_MILLIS)).await;
-    }
 }
 
 mod connection {
@@ -409,14 +648,19 @@ mod connection {
     static CONFIG: Lazy<ExampleConfig> =
         Lazy::new(|| ExampleConfig::load(Environment::Test).unwrap());
 
+    pub(super) fn get_redis_config() -> &'static RedisConfig {
+        &CONFIG.redis
+    }
+
     static REDIS_POOL: OnceCell<Pool> = OnceCell::const_new();
-    pub(super) async fn get_redis_connection() -> anyhow::Result<Connection> {
+    pub(super) async fn get_redis_pool() -> &'static Pool {
         REDIS_POOL
             .get_or_init(|| async { CONFIG.redis.init_pool().await.unwrap() })
             .await
-            .get()
-            .await
-            .map_err(|err| err.into())
+    }
+
+    pub(super) async fn get_redis_connection() -> anyhow::Result<Connection> {
+        get_redis_pool().await.get().await.map_err(|err| err.into())
     }
 }
 